@@ -0,0 +1,11 @@
+//! Image processing engine behind Fairplay.
+//!
+//! Holds the [`Modifier`](models::modifier::Modifier) model, its serialized
+//! [`Pipeline`](models::pipeline::Pipeline) form and a synchronous [`apply`] that runs a modifier
+//! stack on an [`Rgba32FImage`](image::Rgba32FImage). Nothing in here depends on a GUI toolkit.
+
+pub mod models;
+pub mod services;
+
+pub use services::cache::StageCache;
+pub use services::image::{apply, from_rgba8, histogram, histogram_with_buckets, to_rgba8};
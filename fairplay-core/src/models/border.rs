@@ -0,0 +1,83 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::Error;
+
+/// How neighbourhood modifiers read pixels that lie outside of the image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum BorderMode {
+    /// Repeats the closest edge pixel.
+    #[default]
+    Clamp,
+    /// Reflects the image at its edges, without repeating the edge pixel.
+    Mirror,
+    /// Continues with the opposite side of the image.
+    Wrap,
+    /// Uses the constant colour of [`Border::color`].
+    Constant,
+    /// Leaves the pixels out. Convolution kernels use the centre pixel instead, so that they
+    /// keep their weight.
+    Skip
+}
+
+impl BorderMode {
+    pub const ALL: [BorderMode; 5] = [BorderMode::Clamp, BorderMode::Mirror, BorderMode::Wrap, BorderMode::Constant, BorderMode::Skip];
+
+    /// Name in pipelines and on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            BorderMode::Clamp => { "clamp" }
+            BorderMode::Mirror => { "mirror" }
+            BorderMode::Wrap => { "wrap" }
+            BorderMode::Constant => { "constant" }
+            BorderMode::Skip => { "skip" }
+        }
+    }
+}
+
+impl Display for BorderMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(
+            match self {
+                BorderMode::Clamp => { "Clamp" }
+                BorderMode::Mirror => { "Mirror" }
+                BorderMode::Wrap => { "Wrap" }
+                BorderMode::Constant => { "Constant colour" }
+                BorderMode::Skip => { "Skip" }
+            }
+        )
+    }
+}
+
+impl FromStr for BorderMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        BorderMode::ALL.into_iter().find(|mode| mode.name() == s).ok_or(())
+    }
+}
+
+// Written as a plain string: ron can't read unit variants back inside the internally tagged
+// `Modifier`.
+impl Serialize for BorderMode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for BorderMode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        BorderMode::from_str(&name).map_err(|_| D::Error::unknown_variant(&name, &["clamp", "mirror", "wrap", "constant", "skip"]))
+    }
+}
+
+/// Edge handling shared by all neighbourhood modifiers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Border {
+    pub mode: BorderMode,
+    /// RGB colour of [`BorderMode::Constant`]. It is opaque.
+    pub color: [u8; 3]
+}
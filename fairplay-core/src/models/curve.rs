@@ -0,0 +1,48 @@
+use std::fmt::{Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+/// Control point of a curve, both coordinates are in `0.0..=1.0`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CurvePoint {
+    pub x: f32,
+    pub y: f32
+}
+
+impl CurvePoint {
+    pub fn new(x: f32, y: f32) -> Self {
+        CurvePoint { x, y }
+    }
+}
+
+/// The curves of [`CurvesOptions`](crate::models::modifier::CurvesOptions) and the levels of
+/// [`LevelsOptions`](crate::models::modifier::LevelsOptions).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum CurveChannel {
+    /// Applied to red, green and blue alike, before their own curves.
+    #[default]
+    Rgb,
+    Red,
+    Green,
+    Blue,
+    /// Applied last to the weighted grey value, shifting all channels by the same amount.
+    Luminance
+}
+
+impl CurveChannel {
+    pub const ALL: [CurveChannel; 5] = [CurveChannel::Rgb, CurveChannel::Red, CurveChannel::Green, CurveChannel::Blue, CurveChannel::Luminance];
+}
+
+impl Display for CurveChannel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(
+            match self {
+                CurveChannel::Rgb => { "RGB" }
+                CurveChannel::Red => { "Red" }
+                CurveChannel::Green => { "Green" }
+                CurveChannel::Blue => { "Blue" }
+                CurveChannel::Luminance => { "Luminance" }
+            }
+        )
+    }
+}
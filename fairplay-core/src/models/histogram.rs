@@ -0,0 +1,57 @@
+/// Pixel counts of an image split into equally wide buckets per channel.
+#[derive(Default, Clone, Debug)]
+pub struct Histogram {
+    pub lightness: Vec<u32>,
+    pub red: Vec<u32>,
+    pub green: Vec<u32>,
+    pub blue: Vec<u32>
+}
+
+impl Histogram {
+    /// Darkest and brightest value of `counts` in `0.0..=1.0`, leaving out `clip` percent of the
+    /// pixels on either side. Values are interpolated within buckets. `None` without pixels.
+    pub fn clipped_range(counts: &[u32], clip: f32) -> Option<(f32, f32)> {
+        let total: u64 = counts.iter().map(|c| *c as u64).sum();
+        if total == 0 {
+            return None;
+        }
+        let clipped = total as f64 * (clip.clamp(0.0, 50.0) as f64 / 100.0);
+        let buckets = counts.len() as f64;
+
+        // Position of the `clipped`-th pixel counted from the start of `counts`.
+        let position = |counts: &mut dyn Iterator<Item = &u32>| {
+            let mut seen = 0.0;
+            for (i, count) in counts.enumerate() {
+                let count = *count as f64;
+                if count > 0.0 && seen + count > clipped {
+                    return (i as f64 + (clipped - seen) / count) / buckets;
+                }
+                seen += count;
+            }
+            1.0
+        };
+
+        let low = position(&mut counts.iter());
+        let high = 1.0 - position(&mut counts.iter().rev());
+        Some((low as f32, high.max(low) as f32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clipped_range_skips_empty_and_clipped_buckets() {
+        let mut counts = vec![0; 32];
+        counts[4] = 100;
+        counts[20] = 100;
+        let (low, high) = Histogram::clipped_range(&counts, 0.0).unwrap();
+        assert_eq!((low, high), (4.0 / 32.0, 21.0 / 32.0));
+
+        let (low, high) = Histogram::clipped_range(&counts, 25.0).unwrap();
+        assert_eq!((low, high), (4.5 / 32.0, 20.5 / 32.0));
+
+        assert_eq!(Histogram::clipped_range(&[0; 32], 1.0), None);
+    }
+}
@@ -0,0 +1,133 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::Error;
+
+/// Colour model in which saturation and lightness are changed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ColorModel {
+    /// Full saturation reaches white at full lightness.
+    #[default]
+    Hsl,
+    /// Full value keeps the colour at its brightest.
+    Hsv
+}
+
+impl ColorModel {
+    pub const ALL: [ColorModel; 2] = [ColorModel::Hsl, ColorModel::Hsv];
+
+    /// Name in pipelines and on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorModel::Hsl => { "hsl" }
+            ColorModel::Hsv => { "hsv" }
+        }
+    }
+}
+
+impl Display for ColorModel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(
+            match self {
+                ColorModel::Hsl => { "HSL" }
+                ColorModel::Hsv => { "HSV" }
+            }
+        )
+    }
+}
+
+impl FromStr for ColorModel {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ColorModel::ALL.into_iter().find(|model| model.name() == s).ok_or(())
+    }
+}
+
+// Written as a plain string, like `BorderMode`.
+impl Serialize for ColorModel {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for ColorModel {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        ColorModel::from_str(&name).map_err(|_| D::Error::unknown_variant(&name, &["hsl", "hsv"]))
+    }
+}
+
+/// Changes to the hue, saturation and lightness of the colours of a [`HueRange`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HueAdjustment {
+    /// Rotation in degrees, `-180..=180`.
+    pub hue: i16,
+    /// `-100..=100`, -100 removes all colour and 100 saturates fully.
+    pub saturation: i16,
+    /// `-100..=100`, -100 gives black and 100 white.
+    pub lightness: i16
+}
+
+/// The adjustments of [`HueSaturationOptions`](crate::models::modifier::HueSaturationOptions).
+/// Every colour range is centred on its hue and fades out towards the neighbouring ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum HueRange {
+    /// Applies to all colours.
+    #[default]
+    Master,
+    Reds,
+    Yellows,
+    Greens,
+    Cyans,
+    Blues,
+    Magentas
+}
+
+impl HueRange {
+    pub const ALL: [HueRange; 7] = [HueRange::Master, HueRange::Reds, HueRange::Yellows, HueRange::Greens, HueRange::Cyans, HueRange::Blues, HueRange::Magentas];
+
+    /// Prefix of the options of the range on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            HueRange::Master => { "master" }
+            HueRange::Reds => { "reds" }
+            HueRange::Yellows => { "yellows" }
+            HueRange::Greens => { "greens" }
+            HueRange::Cyans => { "cyans" }
+            HueRange::Blues => { "blues" }
+            HueRange::Magentas => { "magentas" }
+        }
+    }
+
+    /// Hue in degrees the range is centred on, `None` for [`HueRange::Master`].
+    pub fn centre(self) -> Option<f32> {
+        match self {
+            HueRange::Master => { None }
+            HueRange::Reds => { Some(0.0) }
+            HueRange::Yellows => { Some(60.0) }
+            HueRange::Greens => { Some(120.0) }
+            HueRange::Cyans => { Some(180.0) }
+            HueRange::Blues => { Some(240.0) }
+            HueRange::Magentas => { Some(300.0) }
+        }
+    }
+}
+
+impl Display for HueRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(
+            match self {
+                HueRange::Master => { "Master" }
+                HueRange::Reds => { "Reds" }
+                HueRange::Yellows => { "Yellows" }
+                HueRange::Greens => { "Greens" }
+                HueRange::Cyans => { "Cyans" }
+                HueRange::Blues => { "Blues" }
+                HueRange::Magentas => { "Magentas" }
+            }
+        )
+    }
+}
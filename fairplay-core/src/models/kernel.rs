@@ -0,0 +1,66 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use crate::models::border::Border;
+use crate::models::modifier::CustomKernelOptions;
+
+/// Built-in kernels to start a custom kernel from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KernelPreset {
+    Emboss,
+    EdgeEnhance,
+    Outline,
+    Ridge
+}
+
+impl KernelPreset {
+    pub const ALL: [KernelPreset; 4] = [KernelPreset::Emboss, KernelPreset::EdgeEnhance, KernelPreset::Outline, KernelPreset::Ridge];
+
+    /// Name on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            KernelPreset::Emboss => { "emboss" }
+            KernelPreset::EdgeEnhance => { "edge-enhance" }
+            KernelPreset::Outline => { "outline" }
+            KernelPreset::Ridge => { "ridge" }
+        }
+    }
+
+    pub fn options(self) -> CustomKernelOptions {
+        let (kernel, divisor, bias) = match self {
+            KernelPreset::Emboss => { ([[-1.0, -1.0, 0.0], [-1.0, 0.0, 1.0], [0.0, 1.0, 1.0]], 1.0, 128.0) }
+            KernelPreset::EdgeEnhance => { ([[-1.0, -1.0, -1.0], [-1.0, 10.0, -1.0], [-1.0, -1.0, -1.0]], 2.0, 0.0) }
+            KernelPreset::Outline => { ([[-1.0, -1.0, -1.0], [-1.0, 8.0, -1.0], [-1.0, -1.0, -1.0]], 1.0, 0.0) }
+            KernelPreset::Ridge => { ([[0.0, -1.0, 0.0], [-1.0, 4.0, -1.0], [0.0, -1.0, 0.0]], 1.0, 0.0) }
+        };
+
+        CustomKernelOptions {
+            kernel: kernel.iter().map(|row| row.to_vec()).collect(),
+            divisor,
+            bias,
+            normalize: false,
+            border: Border::default(),
+        }
+    }
+}
+
+impl Display for KernelPreset {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(
+            match self {
+                KernelPreset::Emboss => { "Emboss" }
+                KernelPreset::EdgeEnhance => { "Edge enhance" }
+                KernelPreset::Outline => { "Outline" }
+                KernelPreset::Ridge => { "Ridge" }
+            }
+        )
+    }
+}
+
+impl FromStr for KernelPreset {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        KernelPreset::ALL.into_iter().find(|preset| preset.name() == s).ok_or(())
+    }
+}
@@ -0,0 +1,24 @@
+use crate::models::modifier::Modifier;
+
+/// An entry of a modifier stack. Disabled layers keep their options but are skipped when the
+/// stack is applied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layer {
+    pub modifier: Modifier,
+    pub enabled: bool
+}
+
+impl Layer {
+    pub fn new(modifier: Modifier) -> Self {
+        Layer {
+            modifier,
+            enabled: true,
+        }
+    }
+}
+
+impl From<Modifier> for Layer {
+    fn from(modifier: Modifier) -> Self {
+        Layer::new(modifier)
+    }
+}
@@ -0,0 +1,8 @@
+pub mod modifier;
+pub mod layer;
+pub mod pipeline;
+pub mod histogram;
+pub mod border;
+pub mod kernel;
+pub mod curve;
+pub mod hue;
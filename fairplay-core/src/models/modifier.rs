@@ -0,0 +1,883 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::border::{Border, BorderMode};
+use crate::models::curve::{CurveChannel, CurvePoint};
+use crate::models::histogram::Histogram;
+use crate::models::hue::{ColorModel, HueAdjustment, HueRange};
+use crate::models::kernel::KernelPreset;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Modifier {
+    Negative(NegativeOptions),
+    Thresholding(ThresholdingOptions),
+    Grayscale(GrayscaleOptions),
+    Channels(ChannelOptions),
+    LightnessCorrection(LightnessCorrectionOptions),
+    BoxBlur(BoxBlurOptions),
+    GaussianBlur(GaussianBlurOptions),
+    MedianBlur(MedianBlurOptions),
+    Sobel(SobelOptions),
+    Laplace(LaplaceOptions),
+    Sharpening(SharpeningOptions),
+    UnsharpMasking(UnsharpMaskingOptions),
+    CustomKernel(CustomKernelOptions),
+    Curves(CurvesOptions),
+    Levels(LevelsOptions),
+    HueSaturation(HueSaturationOptions)
+}
+
+impl Display for Modifier {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(
+            match self {
+                Modifier::Grayscale(_) => { "Grayscale" }
+                Modifier::Negative(_) => { "Negative" }
+                Modifier::Thresholding(_) => { "Thresholding" }
+                Modifier::Channels(_) => { "Channels" }
+                Modifier::LightnessCorrection(_) => { "Lightness correction" }
+                Modifier::BoxBlur(_) => { "Box blur" }
+                Modifier::GaussianBlur(_) => { "Gaussian blur" }
+                Modifier::MedianBlur(_) => { "Median blur" }
+                Modifier::Sobel(_) => { "Sobel" }
+                Modifier::Laplace(_) => { "Laplace" }
+                Modifier::Sharpening(_) => { "Sharpening" }
+                Modifier::UnsharpMasking(_) => { "Unsharp masking" }
+                Modifier::CustomKernel(_) => { "Custom kernel" }
+                Modifier::Curves(_) => { "Curves" }
+                Modifier::Levels(_) => { "Levels" }
+                Modifier::HueSaturation(_) => { "Hue / saturation" }
+            }
+        )
+    }
+}
+
+impl Modifier {
+    /// Switches to the edge handling the modifier had before borders were selectable, for files
+    /// saved back then. The blurs and unsharp masking left out-of-range pixels out, while the
+    /// 3×3 kernels repeated the edge.
+    pub fn use_legacy_border(&mut self) {
+        match self {
+            Modifier::BoxBlur(opts) => { opts.border.mode = BorderMode::Skip }
+            Modifier::GaussianBlur(opts) => { opts.border.mode = BorderMode::Skip }
+            Modifier::MedianBlur(opts) => { opts.border.mode = BorderMode::Skip }
+            Modifier::UnsharpMasking(opts) => { opts.border.mode = BorderMode::Skip }
+            Modifier::Sobel(opts) => { opts.border.mode = BorderMode::Clamp }
+            Modifier::Laplace(opts) => { opts.border.mode = BorderMode::Clamp }
+            Modifier::Sharpening(opts) => { opts.border.mode = BorderMode::Clamp }
+            _ => {}
+        }
+    }
+
+    /// Checks the options the types allow but the modifier can't run with, which can only come
+    /// from hand-edited files: even blur sizes and malformed kernels.
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Modifier::BoxBlur(opts) => { odd_size("size", opts.size).map(|_| ()) }
+            Modifier::GaussianBlur(opts) => { odd_size("size", opts.size).map(|_| ()) }
+            Modifier::MedianBlur(opts) => { odd_size("size", opts.size).map(|_| ()) }
+            Modifier::UnsharpMasking(opts) => { odd_size("blur_size", opts.blur_size).map(|_| ()) }
+            Modifier::CustomKernel(opts) if !opts.is_valid() => {
+                Err(String::from("'kernel' must be a square matrix with an odd size"))
+            }
+            Modifier::Levels(opts) => {
+                let channels = [("", &opts.rgb), ("red_", &opts.red), ("green_", &opts.green), ("blue_", &opts.blue), ("luminance_", &opts.luminance)];
+                channels.iter().try_for_each(|(prefix, levels)| positive_gamma(&format!("{}gamma", prefix), levels.gamma).map(|_| ()))
+            }
+            _ => { Ok(()) }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NegativeOptions {
+    pub grayscale: bool
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThresholdingOptions {
+    pub grayscale: bool,
+    pub threshold: u8
+}
+
+impl Default for ThresholdingOptions {
+    fn default() -> Self {
+        ThresholdingOptions {
+            grayscale: false,
+            threshold: u8::MAX / 2,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GrayscaleOptions {
+    pub red_weight: u8,
+    pub blue_weight: u8,
+    pub green_weight: u8
+}
+
+impl Default for GrayscaleOptions {
+    fn default() -> Self {
+        GrayscaleOptions {
+            red_weight: 72,
+            green_weight: 149,
+            blue_weight: 34,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChannelOptions {
+    pub red_enabled: bool,
+    pub red_weight: u8,
+    pub blue_enabled: bool,
+    pub blue_weight: u8,
+    pub green_enabled: bool,
+    pub green_weight: u8
+}
+
+impl Default for ChannelOptions {
+    fn default() -> Self {
+        ChannelOptions {
+            red_enabled: true,
+            red_weight: 100,
+            green_weight: 100,
+            blue_weight: 100,
+            blue_enabled: true,
+            green_enabled: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LightnessCorrectionOptions {
+    pub exponent: u8
+}
+
+impl Default for LightnessCorrectionOptions {
+    fn default() -> Self {
+        LightnessCorrectionOptions {
+            exponent: u8::MAX / 2,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BoxBlurOptions {
+    pub size: u8,
+    pub border: Border
+}
+
+impl Default for BoxBlurOptions {
+    fn default() -> Self {
+        BoxBlurOptions {
+            size: 3,
+            border: Border::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GaussianBlurOptions {
+    pub size: u8,
+    pub border: Border
+}
+
+impl Default for GaussianBlurOptions {
+    fn default() -> Self {
+        GaussianBlurOptions {
+            size: 3,
+            border: Border::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MedianBlurOptions {
+    pub size: u8,
+    pub border: Border
+}
+
+impl Default for MedianBlurOptions {
+    fn default() -> Self {
+        MedianBlurOptions {
+            size: 3,
+            border: Border::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SobelOptions {
+    pub horizontal: bool,
+    pub vertical: bool,
+    pub border: Border
+}
+
+impl Default for SobelOptions {
+    fn default() -> Self {
+        SobelOptions {
+            horizontal: true,
+            vertical: true,
+            border: Border::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LaplaceOptions {
+    pub border: Border
+}
+
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SharpeningOptions {
+    pub border: Border
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UnsharpMaskingOptions {
+    pub blur_size: u8,
+    pub border: Border
+}
+
+impl Default for UnsharpMaskingOptions {
+    fn default() -> Self {
+        UnsharpMaskingOptions {
+            blur_size: 3,
+            border: Border::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CustomKernelOptions {
+    /// Rows of a square matrix with an odd size.
+    pub kernel: Vec<Vec<f32>>,
+    pub divisor: f32,
+    /// Added after dividing, in 0..=255 steps.
+    pub bias: f32,
+    /// Divides by the sum of the weights instead of `divisor`, unless they add up to 0.
+    pub normalize: bool,
+    pub border: Border
+}
+
+impl CustomKernelOptions {
+    pub fn size(&self) -> usize {
+        self.kernel.len()
+    }
+
+    /// Whether the kernel is a square matrix with an odd size.
+    pub fn is_valid(&self) -> bool {
+        !self.size().is_multiple_of(2) && self.kernel.iter().all(|row| row.len() == self.size())
+    }
+
+    /// The kernel with `size` rows and columns. Weights are kept around the centre, new ones are 0.
+    pub fn resized(&self, size: usize) -> CustomKernelOptions {
+        let offset = (self.size() as i64 - size as i64) / 2;
+        let weight = |x: usize, y: usize| {
+            let (x, y) = (x as i64 + offset, y as i64 + offset);
+            if x < 0 || y < 0 { return 0.0 }
+            self.kernel.get(y as usize).and_then(|row| row.get(x as usize)).copied().unwrap_or(0.0)
+        };
+
+        CustomKernelOptions {
+            kernel: (0..size).map(|y| (0..size).map(|x| weight(x, y)).collect()).collect(),
+            ..self.clone()
+        }
+    }
+
+    /// What the weighted sum is divided by. Never 0.
+    pub fn effective_divisor(&self) -> f32 {
+        let sum: f32 = self.kernel.iter().flatten().sum();
+        match (self.normalize, sum, self.divisor) {
+            (true, sum, _) if sum != 0.0 => { sum }
+            (_, _, divisor) if divisor != 0.0 => { divisor }
+            _ => { 1.0 }
+        }
+    }
+}
+
+impl Default for CustomKernelOptions {
+    fn default() -> Self {
+        CustomKernelOptions {
+            kernel: vec![vec![0.0, 0.0, 0.0], vec![0.0, 1.0, 0.0], vec![0.0, 0.0, 0.0]],
+            divisor: 1.0,
+            bias: 0.0,
+            normalize: false,
+            border: Border::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CurvesOptions {
+    pub rgb: Vec<CurvePoint>,
+    pub red: Vec<CurvePoint>,
+    pub green: Vec<CurvePoint>,
+    pub blue: Vec<CurvePoint>,
+    pub luminance: Vec<CurvePoint>
+}
+
+impl CurvesOptions {
+    pub fn curve(&self, channel: CurveChannel) -> &Vec<CurvePoint> {
+        match channel {
+            CurveChannel::Rgb => { &self.rgb }
+            CurveChannel::Red => { &self.red }
+            CurveChannel::Green => { &self.green }
+            CurveChannel::Blue => { &self.blue }
+            CurveChannel::Luminance => { &self.luminance }
+        }
+    }
+
+    pub fn curve_mut(&mut self, channel: CurveChannel) -> &mut Vec<CurvePoint> {
+        match channel {
+            CurveChannel::Rgb => { &mut self.rgb }
+            CurveChannel::Red => { &mut self.red }
+            CurveChannel::Green => { &mut self.green }
+            CurveChannel::Blue => { &mut self.blue }
+            CurveChannel::Luminance => { &mut self.luminance }
+        }
+    }
+}
+
+impl Default for CurvesOptions {
+    fn default() -> Self {
+        let identity = vec![CurvePoint::new(0.0, 0.0), CurvePoint::new(1.0, 1.0)];
+        CurvesOptions {
+            rgb: identity.clone(),
+            red: identity.clone(),
+            green: identity.clone(),
+            blue: identity.clone(),
+            luminance: identity,
+        }
+    }
+}
+
+/// Levels of one channel. Input points are stretched to the output points.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChannelLevels {
+    pub input_black: u8,
+    pub input_white: u8,
+    /// Above 1 brightens the midtones, below 1 darkens them.
+    pub gamma: f32,
+    pub output_black: u8,
+    pub output_white: u8
+}
+
+impl Default for ChannelLevels {
+    fn default() -> Self {
+        ChannelLevels {
+            input_black: 0,
+            input_white: u8::MAX,
+            gamma: 1.0,
+            output_black: 0,
+            output_white: u8::MAX,
+        }
+    }
+}
+
+/// Levels for the channels of [`CurveChannel`], applied in the same order as curves.
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LevelsOptions {
+    pub rgb: ChannelLevels,
+    pub red: ChannelLevels,
+    pub green: ChannelLevels,
+    pub blue: ChannelLevels,
+    pub luminance: ChannelLevels
+}
+
+impl LevelsOptions {
+    pub fn levels(&self, channel: CurveChannel) -> &ChannelLevels {
+        match channel {
+            CurveChannel::Rgb => { &self.rgb }
+            CurveChannel::Red => { &self.red }
+            CurveChannel::Green => { &self.green }
+            CurveChannel::Blue => { &self.blue }
+            CurveChannel::Luminance => { &self.luminance }
+        }
+    }
+
+    pub fn levels_mut(&mut self, channel: CurveChannel) -> &mut ChannelLevels {
+        match channel {
+            CurveChannel::Rgb => { &mut self.rgb }
+            CurveChannel::Red => { &mut self.red }
+            CurveChannel::Green => { &mut self.green }
+            CurveChannel::Blue => { &mut self.blue }
+            CurveChannel::Luminance => { &mut self.luminance }
+        }
+    }
+
+    /// Levels that stretch every colour channel of an image with `histogram` to the full range,
+    /// clipping `clip` percent of the pixels on either side. Everything else is reset.
+    /// `histogram` has one bucket per 8 bit level, so 256 per channel.
+    pub fn auto(histogram: &Histogram, clip: f32) -> LevelsOptions {
+        let stretched = |counts: &[u32]| {
+            let Some((low, high)) = Histogram::clipped_range(counts, clip) else { return ChannelLevels::default() };
+            let levels = counts.len() as f32;
+            let input_black = (low * levels).floor().min(254.0) as u8;
+            ChannelLevels {
+                input_black,
+                input_white: (((high * levels).ceil() - 1.0) as u8).max(input_black + 1),
+                ..ChannelLevels::default()
+            }
+        };
+
+        LevelsOptions {
+            red: stretched(&histogram.red),
+            green: stretched(&histogram.green),
+            blue: stretched(&histogram.blue),
+            ..LevelsOptions::default()
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HueSaturationOptions {
+    pub model: ColorModel,
+    pub master: HueAdjustment,
+    pub reds: HueAdjustment,
+    pub yellows: HueAdjustment,
+    pub greens: HueAdjustment,
+    pub cyans: HueAdjustment,
+    pub blues: HueAdjustment,
+    pub magentas: HueAdjustment,
+    /// Gives every pixel the hue of `master` and the saturation of `master` (where 0 is 50%),
+    /// ignoring the colour ranges.
+    pub colorize: bool
+}
+
+impl HueSaturationOptions {
+    pub fn adjustment(&self, range: HueRange) -> &HueAdjustment {
+        match range {
+            HueRange::Master => { &self.master }
+            HueRange::Reds => { &self.reds }
+            HueRange::Yellows => { &self.yellows }
+            HueRange::Greens => { &self.greens }
+            HueRange::Cyans => { &self.cyans }
+            HueRange::Blues => { &self.blues }
+            HueRange::Magentas => { &self.magentas }
+        }
+    }
+
+    pub fn adjustment_mut(&mut self, range: HueRange) -> &mut HueAdjustment {
+        match range {
+            HueRange::Master => { &mut self.master }
+            HueRange::Reds => { &mut self.reds }
+            HueRange::Yellows => { &mut self.yellows }
+            HueRange::Greens => { &mut self.greens }
+            HueRange::Cyans => { &mut self.cyans }
+            HueRange::Blues => { &mut self.blues }
+            HueRange::Magentas => { &mut self.magentas }
+        }
+    }
+}
+
+impl FromStr for Modifier {
+    type Err = String;
+
+    /// Parses a modifier from its command line form, e.g. `gaussian-blur:size=5` or
+    /// `channels:red_weight=150,blue_enabled=false`. Options that are left out keep their defaults.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, params) = s.split_once(':').unwrap_or((s, ""));
+        let mut params = Params::parse(params)?;
+
+        let modifier = match name {
+            "negative" => {
+                let d = NegativeOptions::default();
+                Modifier::Negative(NegativeOptions {
+                    grayscale: params.take("grayscale", d.grayscale)?,
+                })
+            }
+            "thresholding" => {
+                let d = ThresholdingOptions::default();
+                Modifier::Thresholding(ThresholdingOptions {
+                    grayscale: params.take("grayscale", d.grayscale)?,
+                    threshold: params.take("threshold", d.threshold)?,
+                })
+            }
+            "grayscale" => {
+                let d = GrayscaleOptions::default();
+                Modifier::Grayscale(GrayscaleOptions {
+                    red_weight: params.take("red_weight", d.red_weight)?,
+                    green_weight: params.take("green_weight", d.green_weight)?,
+                    blue_weight: params.take("blue_weight", d.blue_weight)?,
+                })
+            }
+            "channels" => {
+                let d = ChannelOptions::default();
+                Modifier::Channels(ChannelOptions {
+                    red_enabled: params.take("red_enabled", d.red_enabled)?,
+                    red_weight: params.take("red_weight", d.red_weight)?,
+                    green_enabled: params.take("green_enabled", d.green_enabled)?,
+                    green_weight: params.take("green_weight", d.green_weight)?,
+                    blue_enabled: params.take("blue_enabled", d.blue_enabled)?,
+                    blue_weight: params.take("blue_weight", d.blue_weight)?,
+                })
+            }
+            "lightness-correction" => {
+                let d = LightnessCorrectionOptions::default();
+                Modifier::LightnessCorrection(LightnessCorrectionOptions {
+                    exponent: params.take("exponent", d.exponent)?,
+                })
+            }
+            "box-blur" => {
+                let d = BoxBlurOptions::default();
+                Modifier::BoxBlur(BoxBlurOptions {
+                    size: params.take_size("size", d.size)?,
+                    border: params.take_border(d.border)?,
+                })
+            }
+            "gaussian-blur" => {
+                let d = GaussianBlurOptions::default();
+                Modifier::GaussianBlur(GaussianBlurOptions {
+                    size: params.take_size("size", d.size)?,
+                    border: params.take_border(d.border)?,
+                })
+            }
+            "median-blur" => {
+                let d = MedianBlurOptions::default();
+                Modifier::MedianBlur(MedianBlurOptions {
+                    size: params.take_size("size", d.size)?,
+                    border: params.take_border(d.border)?,
+                })
+            }
+            "sobel" => {
+                let d = SobelOptions::default();
+                Modifier::Sobel(SobelOptions {
+                    horizontal: params.take("horizontal", d.horizontal)?,
+                    vertical: params.take("vertical", d.vertical)?,
+                    border: params.take_border(d.border)?,
+                })
+            }
+            "laplace" => {
+                let d = LaplaceOptions::default();
+                Modifier::Laplace(LaplaceOptions {
+                    border: params.take_border(d.border)?,
+                })
+            }
+            "sharpening" => {
+                let d = SharpeningOptions::default();
+                Modifier::Sharpening(SharpeningOptions {
+                    border: params.take_border(d.border)?,
+                })
+            }
+            "unsharp-masking" => {
+                let d = UnsharpMaskingOptions::default();
+                Modifier::UnsharpMasking(UnsharpMaskingOptions {
+                    blur_size: params.take_size("blur_size", d.blur_size)?,
+                    border: params.take_border(d.border)?,
+                })
+            }
+            "custom-kernel" => {
+                let d = params.take_opt("preset")?.map(KernelPreset::options).unwrap_or_default();
+                Modifier::CustomKernel(CustomKernelOptions {
+                    kernel: params.take("kernel", KernelRows(d.kernel))?.0,
+                    divisor: params.take("divisor", d.divisor)?,
+                    bias: params.take("bias", d.bias)?,
+                    normalize: params.take("normalize", d.normalize)?,
+                    border: params.take_border(d.border)?,
+                })
+            }
+            "curves" => {
+                let d = CurvesOptions::default();
+                Modifier::Curves(CurvesOptions {
+                    rgb: params.take("rgb", CurvePoints(d.rgb))?.0,
+                    red: params.take("red", CurvePoints(d.red))?.0,
+                    green: params.take("green", CurvePoints(d.green))?.0,
+                    blue: params.take("blue", CurvePoints(d.blue))?.0,
+                    luminance: params.take("luminance", CurvePoints(d.luminance))?.0,
+                })
+            }
+            "levels" => {
+                let d = LevelsOptions::default();
+                Modifier::Levels(LevelsOptions {
+                    rgb: params.take_levels("", d.rgb)?,
+                    red: params.take_levels("red_", d.red)?,
+                    green: params.take_levels("green_", d.green)?,
+                    blue: params.take_levels("blue_", d.blue)?,
+                    luminance: params.take_levels("luminance_", d.luminance)?,
+                })
+            }
+            "hue-saturation" => {
+                let mut opts = HueSaturationOptions {
+                    model: params.take("model", ColorModel::default())?,
+                    colorize: params.take("colorize", false)?,
+                    ..HueSaturationOptions::default()
+                };
+                for range in HueRange::ALL {
+                    *opts.adjustment_mut(range) = params.take_hue(range)?;
+                }
+                Modifier::HueSaturation(opts)
+            }
+            _ => { return Err(format!("Unknown modifier '{}'", name)) }
+        };
+
+        params.finish(name)?;
+        Ok(modifier)
+    }
+}
+
+/// `key=value` pairs of a modifier given on the command line.
+struct Params(Vec<(String, String)>);
+
+impl Params {
+    fn parse(s: &str) -> Result<Self, String> {
+        let mut params = vec![];
+        for param in s.split(',').filter(|p| !p.is_empty()) {
+            let Some((key, value)) = param.split_once('=') else {
+                return Err(format!("Expected 'key=value', got '{}'", param))
+            };
+            params.push((key.trim().replace('-', "_"), value.trim().to_string()));
+        }
+        Ok(Params(params))
+    }
+
+    fn take<T: FromStr>(&mut self, key: &str, default: T) -> Result<T, String> {
+        Ok(self.take_opt(key)?.unwrap_or(default))
+    }
+
+    fn take_opt<T: FromStr>(&mut self, key: &str) -> Result<Option<T>, String> {
+        let Some(idx) = self.0.iter().position(|(k, _)| k == key) else { return Ok(None) };
+        let (_, value) = self.0.remove(idx);
+        value.parse().map(Some).map_err(|_| format!("Invalid value '{}' for '{}'", value, key))
+    }
+
+    fn take_size(&mut self, key: &str, default: u8) -> Result<u8, String> {
+        odd_size(key, self.take(key, default)?)
+    }
+
+    /// `border` takes a mode such as `mirror`, `border_color` a `#rrggbb` colour.
+    fn take_border(&mut self, default: Border) -> Result<Border, String> {
+        Ok(Border {
+            mode: self.take("border", default.mode)?,
+            color: self.take("border_color", HexColor(default.color))?.0,
+        })
+    }
+
+    /// `black`, `white`, `gamma`, `output_black` and `output_white`, each starting with `prefix`.
+    fn take_levels(&mut self, prefix: &str, default: ChannelLevels) -> Result<ChannelLevels, String> {
+        let key = |name: &str| format!("{}{}", prefix, name);
+        let levels = ChannelLevels {
+            input_black: self.take(&key("black"), default.input_black)?,
+            input_white: self.take(&key("white"), default.input_white)?,
+            gamma: self.take(&key("gamma"), default.gamma)?,
+            output_black: self.take(&key("output_black"), default.output_black)?,
+            output_white: self.take(&key("output_white"), default.output_white)?,
+        };
+        positive_gamma(&key("gamma"), levels.gamma)?;
+        Ok(levels)
+    }
+
+    /// `hue`, `saturation` and `lightness`, prefixed with the name of `range` unless it is the
+    /// master range, e.g. `blues_saturation`.
+    fn take_hue(&mut self, range: HueRange) -> Result<HueAdjustment, String> {
+        let key = |name: &str| match range {
+            HueRange::Master => { name.to_string() }
+            range => { format!("{}_{}", range.name(), name) }
+        };
+        let mut limited = |name: &str, limit: i16| {
+            let value: i16 = self.take(&key(name), 0)?;
+            if !(-limit..=limit).contains(&value) {
+                return Err(format!("'{}' must be between -{} and {}, got {}", key(name), limit, limit, value))
+            }
+            Ok(value)
+        };
+        Ok(HueAdjustment {
+            hue: limited("hue", 180)?,
+            saturation: limited("saturation", 100)?,
+            lightness: limited("lightness", 100)?,
+        })
+    }
+
+    fn finish(self, name: &str) -> Result<(), String> {
+        match self.0.first() {
+            Some((key, _)) => { Err(format!("Unknown option '{}' for modifier '{}'", key, name)) }
+            None => { Ok(()) }
+        }
+    }
+}
+
+struct HexColor([u8; 3]);
+
+impl FromStr for HexColor {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix('#').filter(|hex| hex.len() == 6 && hex.is_ascii()).ok_or(())?;
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| ());
+        Ok(HexColor([channel(0)?, channel(2)?, channel(4)?]))
+    }
+}
+
+fn odd_size(key: &str, size: u8) -> Result<u8, String> {
+    if size.is_multiple_of(2) {
+        return Err(format!("'{}' must be an odd number, got {}", key, size))
+    }
+    Ok(size)
+}
+
+fn positive_gamma(key: &str, gamma: f32) -> Result<f32, String> {
+    if !(gamma > 0.0 && gamma.is_finite()) {
+        return Err(format!("'{}' must be above 0, got {}", key, gamma))
+    }
+    Ok(gamma)
+}
+
+/// Kernel rows separated by `;`, with weights separated by spaces, e.g. `0 -1 0;-1 4 -1;0 -1 0`.
+struct KernelRows(Vec<Vec<f32>>);
+
+impl FromStr for KernelRows {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rows = s.split(';')
+            .map(|row| row.split_whitespace().map(|w| w.parse().map_err(|_| ())).collect())
+            .collect::<Result<Vec<Vec<f32>>, ()>>()?;
+        let valid = CustomKernelOptions { kernel: rows, ..CustomKernelOptions::default() };
+        if !valid.is_valid() {
+            return Err(())
+        }
+        Ok(KernelRows(valid.kernel))
+    }
+}
+
+/// Curve points separated by `;`, with `x` and `y` in `0..=1` separated by a space, e.g. `0 0;0.5 0.6;1 1`.
+struct CurvePoints(Vec<CurvePoint>);
+
+impl FromStr for CurvePoints {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut points = vec![];
+        for point in s.split(';') {
+            let coordinates = point.split_whitespace()
+                .map(|v| v.parse::<f32>().ok().filter(|v| (0.0..=1.0).contains(v)).ok_or(()))
+                .collect::<Result<Vec<f32>, ()>>()?;
+            let [x, y] = coordinates[..] else { return Err(()) };
+            points.push(CurvePoint::new(x, y));
+        }
+        Ok(CurvePoints(points))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::border::BorderMode;
+
+    use super::*;
+
+    #[test]
+    fn parses_border_options() {
+        let modifier: Modifier = "box-blur:size=5,border=constant,border_color=#ff8000".parse().unwrap();
+        let border = Border { mode: BorderMode::Constant, color: [255, 128, 0] };
+        assert_eq!(modifier, Modifier::BoxBlur(BoxBlurOptions { size: 5, border }));
+        assert_eq!("laplace".parse::<Modifier>().unwrap(), Modifier::Laplace(LaplaceOptions::default()));
+
+        assert!("sobel:border=diagonal".parse::<Modifier>().is_err());
+        assert!("sharpening:border_color=ff8000".parse::<Modifier>().is_err());
+        assert!("median-blur:border_color=#ff80".parse::<Modifier>().is_err());
+    }
+
+    #[test]
+    fn parses_custom_kernels() {
+        let modifier: Modifier = "custom-kernel:preset=ridge,bias=10,border=wrap".parse().unwrap();
+        let mut expected = KernelPreset::Ridge.options();
+        expected.bias = 10.0;
+        expected.border.mode = BorderMode::Wrap;
+        assert_eq!(modifier, Modifier::CustomKernel(expected));
+
+        let modifier: Modifier = "custom-kernel:kernel=1 2 1;2 4 2;1 2 1,normalize=true".parse().unwrap();
+        let Modifier::CustomKernel(opts) = modifier else { panic!("Parsed {:?}", modifier) };
+        assert_eq!(opts.kernel, vec![vec![1.0, 2.0, 1.0], vec![2.0, 4.0, 2.0], vec![1.0, 2.0, 1.0]]);
+        assert_eq!(opts.effective_divisor(), 16.0);
+
+        assert!("custom-kernel:preset=blur".parse::<Modifier>().is_err());
+        assert!("custom-kernel:kernel=1 2;3 4".parse::<Modifier>().is_err());
+        assert!("custom-kernel:kernel=1 2 3;4 5 6".parse::<Modifier>().is_err());
+    }
+
+    #[test]
+    fn parses_curves() {
+        let modifier: Modifier = "curves:rgb=0 0.1;0.5 0.6;1 1,blue=0 0;1 0.8".parse().unwrap();
+        let Modifier::Curves(opts) = modifier else { panic!("Parsed {:?}", modifier) };
+        assert_eq!(opts.rgb, vec![CurvePoint::new(0.0, 0.1), CurvePoint::new(0.5, 0.6), CurvePoint::new(1.0, 1.0)]);
+        assert_eq!(opts.curve(CurveChannel::Blue), &vec![CurvePoint::new(0.0, 0.0), CurvePoint::new(1.0, 0.8)]);
+        assert_eq!(opts.red, CurvesOptions::default().red);
+
+        assert!("curves:red=0 0;1".parse::<Modifier>().is_err());
+        assert!("curves:green=0 0;1 1.5".parse::<Modifier>().is_err());
+    }
+
+    #[test]
+    fn parses_levels() {
+        let modifier: Modifier = "levels:black=10,gamma=1.5,blue_white=200,luminance_output_black=20".parse().unwrap();
+        let Modifier::Levels(opts) = modifier else { panic!("Parsed {:?}", modifier) };
+        assert_eq!(opts.rgb, ChannelLevels { input_black: 10, gamma: 1.5, ..ChannelLevels::default() });
+        assert_eq!(opts.blue, ChannelLevels { input_white: 200, ..ChannelLevels::default() });
+        assert_eq!(opts.luminance.output_black, 20);
+        assert_eq!(opts.red, ChannelLevels::default());
+
+        assert!("levels:gamma=0".parse::<Modifier>().is_err());
+        assert!("levels:red_black=300".parse::<Modifier>().is_err());
+    }
+
+    #[test]
+    fn auto_levels_stretch_every_channel() {
+        let mut histogram = Histogram { lightness: vec![0; 256], red: vec![0; 256], green: vec![0; 256], blue: vec![0; 256] };
+        histogram.red[64] = 10;
+        histogram.red[127] = 10;
+        histogram.green[0] = 1;
+        histogram.green[255] = 1;
+
+        let opts = LevelsOptions::auto(&histogram, 0.0);
+        assert_eq!((opts.red.input_black, opts.red.input_white), (64, 127));
+        assert_eq!((opts.green.input_black, opts.green.input_white), (0, 255));
+        assert_eq!(opts.blue, ChannelLevels::default());
+        assert_eq!(opts.rgb, ChannelLevels::default());
+    }
+
+    #[test]
+    fn parses_hue_saturation() {
+        let modifier: Modifier = "hue-saturation:model=hsv,hue=-30,blues_saturation=40,reds_lightness=-5".parse().unwrap();
+        let Modifier::HueSaturation(opts) = modifier else { panic!("Parsed {:?}", modifier) };
+        assert_eq!(opts.model, ColorModel::Hsv);
+        assert_eq!(opts.master, HueAdjustment { hue: -30, ..HueAdjustment::default() });
+        assert_eq!(opts.adjustment(HueRange::Blues).saturation, 40);
+        assert_eq!(opts.reds.lightness, -5);
+        assert!(!opts.colorize);
+
+        assert!("hue-saturation:hue=200".parse::<Modifier>().is_err());
+        assert!("hue-saturation:master_hue=20".parse::<Modifier>().is_err());
+        assert!("hue-saturation:model=lab".parse::<Modifier>().is_err());
+    }
+
+    #[test]
+    fn resizes_kernels_around_the_centre() {
+        let opts = KernelPreset::Ridge.options();
+        let grown = opts.resized(5);
+        assert_eq!(grown.kernel[2], vec![0.0, -1.0, 4.0, -1.0, 0.0]);
+        assert_eq!(grown.kernel[0], vec![0.0; 5]);
+        assert_eq!(grown.resized(3), opts);
+        assert_eq!(grown.resized(1).kernel, vec![vec![4.0]]);
+    }
+}
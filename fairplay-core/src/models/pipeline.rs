@@ -0,0 +1,301 @@
+use std::fmt::{Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::layer::Layer;
+use crate::models::modifier::Modifier;
+
+/// Serialized form of a modifier stack, used to save and replay pipelines.
+///
+/// `version` is bumped whenever the format changes in a way older readers can't handle.
+/// Options that are missing from a file fall back to their defaults, so adding an option
+/// doesn't require a new version.
+///
+/// Version history:
+/// 1. Plain list of modifiers.
+/// 2. Adds `disabled`, the indices of modifiers that are switched off.
+/// 3. Adds `border` to neighbourhood modifiers. Older files keep the edge handling they were
+///    made with, see [`Modifier::use_legacy_border`].
+/// 4. Adds the `custom-kernel`, `curves`, `levels` and `hue-saturation` modifiers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Pipeline {
+    pub version: u32,
+    pub modifiers: Vec<Modifier>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disabled: Vec<usize>
+}
+
+/// Just the version, read before the rest so that files from newer versions are rejected
+/// instead of failing on modifiers this version doesn't know.
+#[derive(Deserialize)]
+struct Version {
+    version: u32
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PipelineFormat {
+    Json,
+    Ron
+}
+
+impl PipelineFormat {
+    pub const EXTENSIONS: [&'static str; 2] = ["json", "ron"];
+
+    /// Picks the format from the extension of a file name, defaulting to JSON.
+    pub fn from_file_name(name: &str) -> Self {
+        if name.to_lowercase().ends_with(".ron") { PipelineFormat::Ron } else { PipelineFormat::Json }
+    }
+}
+
+#[derive(Debug)]
+pub enum PipelineError {
+    Json(serde_json::Error),
+    Ron(ron::error::SpannedError),
+    UnsupportedVersion(u32),
+    /// A modifier, counted from 1, with options it can't run with.
+    InvalidModifier(usize, String)
+}
+
+impl Display for PipelineError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PipelineError::Json(e) => { write!(f, "Invalid JSON pipeline: {}", e) }
+            PipelineError::Ron(e) => { write!(f, "Invalid RON pipeline: {}", e) }
+            PipelineError::UnsupportedVersion(v) => {
+                write!(f, "Pipeline version {} is not supported (latest is {})", v, Pipeline::VERSION)
+            }
+            PipelineError::InvalidModifier(i, e) => { write!(f, "Invalid modifier {} in pipeline: {}", i, e) }
+        }
+    }
+}
+
+impl std::error::Error for PipelineError {}
+
+impl Pipeline {
+    pub const VERSION: u32 = 4;
+
+    pub fn new(layers: &[Layer]) -> Self {
+        Pipeline {
+            version: Self::VERSION,
+            modifiers: layers.iter().map(|l| l.modifier.clone()).collect(),
+            disabled: layers.iter().enumerate().filter(|(_, l)| !l.enabled).map(|(i, _)| i).collect(),
+        }
+    }
+
+    pub fn layers(&self) -> Vec<Layer> {
+        self.modifiers.iter()
+            .enumerate()
+            .map(|(i, modifier)| Layer { modifier: modifier.clone(), enabled: !self.disabled.contains(&i) })
+            .collect()
+    }
+
+    pub fn serialize(&self, format: PipelineFormat) -> String {
+        match format {
+            PipelineFormat::Json => { self.to_json() }
+            PipelineFormat::Ron => { self.to_ron() }
+        }
+    }
+
+    pub fn deserialize(s: &str, format: PipelineFormat) -> Result<Self, PipelineError> {
+        match format {
+            PipelineFormat::Json => { Self::from_json(s) }
+            PipelineFormat::Ron => { Self::from_ron(s) }
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Pipeline is always serializable")
+    }
+
+    pub fn from_json(s: &str) -> Result<Self, PipelineError> {
+        Self::check_version(serde_json::from_str::<Version>(s).map_err(PipelineError::Json)?.version)?;
+        serde_json::from_str::<Pipeline>(s).map_err(PipelineError::Json)?.validated()
+    }
+
+    pub fn to_ron(&self) -> String {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).expect("Pipeline is always serializable")
+    }
+
+    pub fn from_ron(s: &str) -> Result<Self, PipelineError> {
+        Self::check_version(ron::from_str::<Version>(s).map_err(PipelineError::Ron)?.version)?;
+        ron::from_str::<Pipeline>(s).map_err(PipelineError::Ron)?.validated()
+    }
+
+    fn check_version(version: u32) -> Result<(), PipelineError> {
+        if version == 0 || version > Self::VERSION {
+            return Err(PipelineError::UnsupportedVersion(version));
+        }
+        Ok(())
+    }
+
+    fn validated(mut self) -> Result<Self, PipelineError> {
+        if self.version < 3 {
+            self.modifiers.iter_mut().for_each(Modifier::use_legacy_border);
+        }
+        for (i, modifier) in self.modifiers.iter().enumerate() {
+            modifier.validate().map_err(|e| PipelineError::InvalidModifier(i + 1, e))?;
+        }
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::border::{Border, BorderMode};
+    use crate::models::curve::CurvePoint;
+    use crate::models::hue::{ColorModel, HueAdjustment};
+    use crate::models::kernel::KernelPreset;
+    use crate::models::modifier::{BoxBlurOptions, ChannelLevels, ChannelOptions, CurvesOptions, CustomKernelOptions, GaussianBlurOptions, GrayscaleOptions, HueSaturationOptions, LaplaceOptions, LevelsOptions, LightnessCorrectionOptions, MedianBlurOptions, NegativeOptions, SharpeningOptions, SobelOptions, ThresholdingOptions, UnsharpMaskingOptions};
+
+    use super::*;
+
+    fn layers(modifiers: Vec<Modifier>) -> Vec<Layer> {
+        modifiers.into_iter().map(Layer::new).collect()
+    }
+
+    fn all_modifiers() -> Pipeline {
+        let mut layers = layers(vec![
+            Modifier::Negative(NegativeOptions { grayscale: true }),
+            Modifier::Thresholding(ThresholdingOptions { grayscale: false, threshold: 42 }),
+            Modifier::Grayscale(GrayscaleOptions { red_weight: 1, green_weight: 2, blue_weight: 3 }),
+            Modifier::Channels(ChannelOptions { red_enabled: false, blue_weight: 200, ..ChannelOptions::default() }),
+            Modifier::LightnessCorrection(LightnessCorrectionOptions { exponent: 10 }),
+            Modifier::BoxBlur(BoxBlurOptions { size: 5, ..BoxBlurOptions::default() }),
+            Modifier::GaussianBlur(GaussianBlurOptions { size: 7, ..GaussianBlurOptions::default() }),
+            Modifier::MedianBlur(MedianBlurOptions { size: 9, ..MedianBlurOptions::default() }),
+            Modifier::Sobel(SobelOptions { horizontal: true, vertical: false, border: Border { mode: BorderMode::Mirror, color: [0; 3] } }),
+            Modifier::Laplace(LaplaceOptions { border: Border { mode: BorderMode::Constant, color: [10, 20, 30] } }),
+            Modifier::Sharpening(SharpeningOptions::default()),
+            Modifier::UnsharpMasking(UnsharpMaskingOptions { blur_size: 11, ..UnsharpMaskingOptions::default() }),
+            Modifier::CustomKernel(CustomKernelOptions { bias: -12.5, normalize: true, ..KernelPreset::Emboss.options().resized(5) }),
+            Modifier::Curves(CurvesOptions { green: vec![CurvePoint::new(0.0, 0.1), CurvePoint::new(0.4, 0.5), CurvePoint::new(1.0, 0.9)], ..CurvesOptions::default() }),
+            Modifier::Levels(LevelsOptions { rgb: ChannelLevels { input_black: 12, gamma: 0.8, ..ChannelLevels::default() }, ..LevelsOptions::default() }),
+            Modifier::HueSaturation(HueSaturationOptions { model: ColorModel::Hsv, cyans: HueAdjustment { hue: -20, saturation: 15, lightness: 0 }, ..HueSaturationOptions::default() }),
+        ]);
+        layers[3].enabled = false;
+        layers[9].enabled = false;
+        Pipeline::new(&layers)
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let pipeline = all_modifiers();
+        assert_eq!(Pipeline::from_json(&pipeline.to_json()).unwrap(), pipeline);
+    }
+
+    #[test]
+    fn ron_round_trip() {
+        let pipeline = all_modifiers();
+        assert_eq!(Pipeline::from_ron(&pipeline.to_ron()).unwrap(), pipeline);
+    }
+
+    #[test]
+    fn json_format_is_stable() {
+        let json = r#"{
+            "version": 4,
+            "modifiers": [
+                { "type": "gaussian-blur", "size": 5 },
+                { "type": "thresholding", "grayscale": true, "threshold": 120 },
+                { "type": "laplace" }
+            ],
+            "disabled": [1]
+        }"#;
+        let mut expected = layers(vec![
+            Modifier::GaussianBlur(GaussianBlurOptions { size: 5, ..GaussianBlurOptions::default() }),
+            Modifier::Thresholding(ThresholdingOptions { grayscale: true, threshold: 120 }),
+            Modifier::Laplace(LaplaceOptions::default()),
+        ]);
+        expected[1].enabled = false;
+        assert_eq!(Pipeline::from_json(json).unwrap().layers(), expected);
+    }
+
+    #[test]
+    fn version_1_is_still_readable() {
+        let json = r#"{ "version": 1, "modifiers": [{ "type": "negative", "grayscale": true }] }"#;
+        let loaded = Pipeline::from_json(json).unwrap();
+        assert_eq!(loaded.layers(), vec![Layer::new(Modifier::Negative(NegativeOptions { grayscale: true }))]);
+    }
+
+    #[test]
+    fn missing_options_use_defaults() {
+        let json = r#"{ "version": 2, "modifiers": [{ "type": "channels", "red_weight": 50 }] }"#;
+        let expected = vec![
+            Modifier::Channels(ChannelOptions { red_weight: 50, ..ChannelOptions::default() }),
+        ];
+        assert_eq!(Pipeline::from_json(json).unwrap().modifiers, expected);
+    }
+
+    #[test]
+    fn border_defaults_to_clamp() {
+        let json = r#"{ "version": 4, "modifiers": [
+            { "type": "median-blur", "size": 5 },
+            { "type": "sobel", "border": { "mode": "wrap" } }
+        ] }"#;
+        let expected = vec![
+            Modifier::MedianBlur(MedianBlurOptions { size: 5, ..MedianBlurOptions::default() }),
+            Modifier::Sobel(SobelOptions { border: Border { mode: BorderMode::Wrap, color: [0; 3] }, ..SobelOptions::default() }),
+        ];
+        let pipeline = Pipeline::from_json(json).unwrap();
+        assert_eq!(pipeline.modifiers, expected);
+        assert_eq!(BorderMode::default(), BorderMode::Clamp);
+    }
+
+    #[test]
+    fn older_versions_keep_their_edge_handling() {
+        let json = r#"{ "version": 2, "modifiers": [
+            { "type": "box-blur", "size": 7 },
+            { "type": "gaussian-blur", "size": 5 },
+            { "type": "median-blur", "size": 5 },
+            { "type": "unsharp-masking", "blur_size": 3 },
+            { "type": "laplace" }
+        ] }"#;
+        let skip = Border { mode: BorderMode::Skip, color: [0; 3] };
+        let expected = vec![
+            Modifier::BoxBlur(BoxBlurOptions { size: 7, border: skip }),
+            Modifier::GaussianBlur(GaussianBlurOptions { size: 5, border: skip }),
+            Modifier::MedianBlur(MedianBlurOptions { size: 5, border: skip }),
+            Modifier::UnsharpMasking(UnsharpMaskingOptions { blur_size: 3, border: skip }),
+            Modifier::Laplace(LaplaceOptions::default()),
+        ];
+        assert_eq!(Pipeline::from_json(json).unwrap().modifiers, expected);
+    }
+
+    #[test]
+    fn newer_version_is_rejected() {
+        let json = r#"{ "version": 999, "modifiers": [] }"#;
+        assert!(matches!(Pipeline::from_json(json), Err(PipelineError::UnsupportedVersion(999))));
+    }
+
+    #[test]
+    fn invalid_options_are_rejected() {
+        let invalid = [
+            r#"{ "type": "gaussian-blur", "size": 0 }"#,
+            r#"{ "type": "box-blur", "size": 4 }"#,
+            r#"{ "type": "unsharp-masking", "blur_size": 6 }"#,
+            r#"{ "type": "custom-kernel", "kernel": [[1, 2], [3, 4]] }"#,
+            r#"{ "type": "custom-kernel", "kernel": [[1, 2, 3], [4, 5], [6, 7, 8]] }"#,
+            r#"{ "type": "levels", "rgb": { "gamma": 0 } }"#,
+            r#"{ "type": "levels", "blue": { "gamma": -1.5 } }"#,
+        ];
+        for modifier in invalid {
+            let json = format!(r#"{{ "version": 4, "modifiers": [{{ "type": "negative" }}, {}] }}"#, modifier);
+            assert!(matches!(Pipeline::from_json(&json), Err(PipelineError::InvalidModifier(2, _))), "{}", modifier);
+        }
+
+        let ron = r#"(version: 4, modifiers: [(type: "median-blur", size: 2)])"#;
+        assert!(matches!(Pipeline::from_ron(ron), Err(PipelineError::InvalidModifier(1, _))));
+    }
+
+    #[test]
+    fn newer_modifiers_are_rejected_by_version() {
+        // A file from a later version with a modifier this one doesn't know yet.
+        let json = r#"{ "version": 5, "modifiers": [{ "type": "vignette", "amount": 30 }] }"#;
+        assert!(matches!(Pipeline::from_json(json), Err(PipelineError::UnsupportedVersion(5))));
+        let ron = r#"(version: 5, modifiers: [(type: "vignette", amount: 30)])"#;
+        assert!(matches!(Pipeline::from_ron(ron), Err(PipelineError::UnsupportedVersion(5))));
+
+        // Files of the current version still report the unknown modifier.
+        let json = r#"{ "version": 4, "modifiers": [{ "type": "vignette" }] }"#;
+        assert!(matches!(Pipeline::from_json(json), Err(PipelineError::Json(_))));
+    }
+}
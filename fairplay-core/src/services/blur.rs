@@ -0,0 +1,251 @@
+use image::{Rgba, Rgba32FImage};
+
+use crate::models::border::Border;
+use crate::services::border::sample;
+use crate::services::parallel;
+
+/// Averages every pixel with the `radius` pixels on both sides of it in its row, with a running
+/// sum so that the cost doesn't depend on the radius. Skipped pixels don't count.
+pub fn box_rows(image: &Rgba32FImage, radius: i64, border: &Border) -> Rgba32FImage {
+    let width = image.width() as i64;
+    let window = 2 * radius as usize + 1;
+
+    parallel::from_rows(image.width(), image.height(), |y, row| {
+        // Prefix sums of the four channels and the pixel count, in f64 so they don't drift.
+        let mut sums = Vec::with_capacity(width as usize + window);
+        let mut total = [0f64; 5];
+        sums.push(total);
+        for x in -radius..width + radius {
+            if let Some(p) = sample(border, image, x, y as i64) {
+                (0..4).for_each(|c| total[c] += p[c] as f64);
+                total[4] += 1.0;
+            }
+            sums.push(total);
+        }
+
+        for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+            let (start, end) = (sums[x], sums[x + window]);
+            let count = end[4] - start[4];
+            (0..4).for_each(|c| pixel[c] = ((end[c] - start[c]) / count) as f32);
+        }
+    })
+}
+
+/// Convolves the colour channels of every row with `kernel`. Skipped pixels read the centre
+/// pixel, and alpha is kept.
+pub fn convolve_rows(image: &Rgba32FImage, kernel: &[f32], border: &Border) -> Rgba32FImage {
+    let radius = (kernel.len() / 2) as i64;
+
+    let width = image.width() as i64;
+
+    parallel::from_fn(image.width(), image.height(), |x, y| {
+        let centre = image.get_pixel(x, y);
+        let mut sum = [0f32; 3];
+        let first = x as i64 - radius;
+        if first >= 0 && first + 2 * radius < width {
+            // Fully inside the image, so the row can be read directly.
+            let start = (y as usize * width as usize + first as usize) * 4;
+            let pixels = image.as_raw()[start..start + kernel.len() * 4].chunks_exact(4);
+            for (p, weight) in pixels.zip(kernel) {
+                (0..3).for_each(|c| sum[c] += p[c] * weight);
+            }
+        } else {
+            for (i, weight) in kernel.iter().enumerate() {
+                let p = sample(border, image, first + i as i64, y as i64).unwrap_or(*centre);
+                (0..3).for_each(|c| sum[c] += p[c] * weight);
+            }
+        }
+        Rgba([sum[0], sum[1], sum[2], centre[3]])
+    })
+}
+
+/// Median of the colour channels in the square window of `radius` around every pixel, using
+/// Huang's sliding histogram: moving one pixel along a row only adds and removes a column.
+/// Values are binned with 16 bit precision. Skipped pixels don't count, and alpha is kept.
+pub fn median(image: &Rgba32FImage, radius: i64, border: &Border) -> Rgba32FImage {
+    parallel::from_rows(image.width(), image.height(), |y, row| {
+        let y = y as i64;
+        let mut histograms = [WindowHistogram::new(), WindowHistogram::new(), WindowHistogram::new()];
+        let inside = y - radius >= 0 && y + radius < image.height() as i64;
+        let update = |histograms: &mut [WindowHistogram; 3], x: i64, add: bool| {
+            for iy in -radius..=radius {
+                let p = if inside && (0..image.width() as i64).contains(&x) {
+                    *image.get_pixel(x as u32, (y + iy) as u32)
+                } else {
+                    let Some(p) = sample(border, image, x, y + iy) else { continue };
+                    p
+                };
+                for (c, histogram) in histograms.iter_mut().enumerate() {
+                    histogram.update(quantize(p[c]), add);
+                }
+            }
+        };
+
+        for x in -radius..=radius {
+            update(&mut histograms, x, true);
+        }
+        for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+            (0..3).for_each(|c| pixel[c] = histograms[c].median());
+            pixel[3] = image.get_pixel(x as u32, y as u32)[3];
+
+            let x = x as i64;
+            update(&mut histograms, x - radius, false);
+            update(&mut histograms, x + radius + 1, true);
+        }
+    })
+}
+
+fn quantize(v: f32) -> u16 {
+    (v.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16
+}
+
+/// Counts of 16 bit values on four levels of 4 bits each, so that a median is found in at
+/// most 64 steps.
+struct WindowHistogram {
+    /// Windows are at most 255 × 255 pixels, so the counts fit.
+    levels: [Vec<u16>; 4],
+    count: u32
+}
+
+impl WindowHistogram {
+    fn new() -> Self {
+        WindowHistogram {
+            levels: [vec![0; 1 << 4], vec![0; 1 << 8], vec![0; 1 << 12], vec![0; 1 << 16]],
+            count: 0,
+        }
+    }
+
+    fn update(&mut self, v: u16, add: bool) {
+        for (i, level) in self.levels.iter_mut().enumerate() {
+            let bin = v as usize >> (12 - 4 * i);
+            if add { level[bin] += 1 } else { level[bin] -= 1 }
+        }
+        if add { self.count += 1 } else { self.count -= 1 }
+    }
+
+    /// The `k`-th smallest value, counting from 0.
+    fn nth(&self, k: u32) -> u16 {
+        let mut seen = 0;
+        let mut bin = 0;
+        for level in &self.levels {
+            let first = bin << 4;
+            bin = first + 15;
+            for (i, count) in level[first..first + 16].iter().enumerate() {
+                if seen + *count as u32 > k {
+                    bin = first + i;
+                    break;
+                }
+                seen += *count as u32;
+            }
+        }
+        bin as u16
+    }
+
+    /// Averages the two middle values for even counts.
+    fn median(&self) -> f32 {
+        let mid = self.count / 2;
+        let v = if self.count.is_multiple_of(2) {
+            (self.nth(mid - 1) as f32 + self.nth(mid) as f32) / 2.0
+        } else {
+            self.nth(mid) as f32
+        };
+        v / u16::MAX as f32
+    }
+}
+
+/// Swaps rows and columns, so that row passes can be used for columns.
+pub fn transpose(image: &Rgba32FImage) -> Rgba32FImage {
+    parallel::from_fn(image.height(), image.width(), |x, y| *image.get_pixel(y, x))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::border::BorderMode;
+
+    use super::*;
+
+    fn source() -> Rgba32FImage {
+        Rgba32FImage::from_fn(13, 9, |x, y| {
+            let v = ((x * 7 + y * 13) % 17) as f32 / 16.0;
+            Rgba([v, 1.0 - v, (x as f32 / 12.0) * v, (y + 1) as f32 / 9.0])
+        })
+    }
+
+    fn borders() -> Vec<Border> {
+        BorderMode::ALL.iter().map(|mode| Border { mode: *mode, color: [200, 10, 90] }).collect()
+    }
+
+    /// The pixels of the window of `radius` around `(x, y)`.
+    fn window(image: &Rgba32FImage, border: &Border, x: u32, y: u32, radius: i64) -> Vec<Rgba<f32>> {
+        let mut pixels = vec![];
+        for iy in -radius..=radius {
+            for ix in -radius..=radius {
+                pixels.extend(sample(border, image, x as i64 + ix, y as i64 + iy));
+            }
+        }
+        pixels
+    }
+
+    fn assert_close(a: &Rgba32FImage, b: &Rgba32FImage, channels: usize, tolerance: f32, border: &Border) {
+        for (pa, pb) in a.pixels().zip(b.pixels()) {
+            for c in 0..channels {
+                assert!((pa[c] - pb[c]).abs() <= tolerance, "{:?}: {:?} != {:?}", border, pa, pb);
+            }
+        }
+    }
+
+    #[test]
+    fn box_blur_matches_the_full_window() {
+        let img = source();
+        for border in borders() {
+            for radius in [1, 4, 12] {
+                let rows = box_rows(&img, radius, &border);
+                let blurred = transpose(&box_rows(&transpose(&rows), radius, &border));
+                let expected = Rgba32FImage::from_fn(img.width(), img.height(), |x, y| {
+                    let pixels = window(&img, &border, x, y, radius);
+                    let mean = |c: usize| pixels.iter().map(|p| p[c]).sum::<f32>() / pixels.len() as f32;
+                    Rgba([mean(0), mean(1), mean(2), mean(3)])
+                });
+                assert_close(&blurred, &expected, 4, 1e-5, &border);
+            }
+        }
+    }
+
+    #[test]
+    fn median_matches_sorting() {
+        let img = source();
+        for border in borders() {
+            for radius in [1, 2, 7] {
+                let filtered = median(&img, radius, &border);
+                let expected = Rgba32FImage::from_fn(img.width(), img.height(), |x, y| {
+                    let pixels = window(&img, &border, x, y, radius);
+                    let median = |c: usize| {
+                        let mut values: Vec<f32> = pixels.iter().map(|p| p[c]).collect();
+                        values.sort_by(f32::total_cmp);
+                        let mid = values.len() / 2;
+                        if values.len().is_multiple_of(2) { (values[mid - 1] + values[mid]) / 2.0 } else { values[mid] }
+                    };
+                    Rgba([median(0), median(1), median(2), img.get_pixel(x, y)[3]])
+                });
+                assert_close(&filtered, &expected, 4, 1.0 / u16::MAX as f32, &border);
+            }
+        }
+    }
+
+    #[test]
+    fn separable_convolution_matches_the_2d_kernel() {
+        let img = source();
+        let kernel = [0.1, 0.2, 0.4, 0.2, 0.1];
+        // Skipped pixels are replaced per pass, so only the other modes are exactly separable.
+        for border in borders().into_iter().filter(|b| b.mode != BorderMode::Skip) {
+            let rows = convolve_rows(&img, &kernel, &border);
+            let convolved = transpose(&convolve_rows(&transpose(&rows), &kernel, &border));
+            let expected = Rgba32FImage::from_fn(img.width(), img.height(), |x, y| {
+                let pixels = window(&img, &border, x, y, 2);
+                let sum = |c: usize| pixels.iter().enumerate().map(|(i, p)| p[c] * kernel[i / 5] * kernel[i % 5]).sum::<f32>();
+                Rgba([sum(0), sum(1), sum(2), img.get_pixel(x, y)[3]])
+            });
+            assert_close(&convolved, &expected, 4, 1e-5, &border);
+        }
+    }
+}
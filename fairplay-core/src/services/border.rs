@@ -0,0 +1,66 @@
+use image::{Rgba, Rgba32FImage};
+
+use crate::models::border::{Border, BorderMode};
+
+/// Reads the pixel at `(x, y)`, which may lie outside of `image`, the way `border` says.
+/// `None` for pixels that are skipped.
+pub fn sample(border: &Border, image: &Rgba32FImage, x: i64, y: i64) -> Option<Rgba<f32>> {
+    let width = image.width() as i64;
+    let height = image.height() as i64;
+    if (0..width).contains(&x) && (0..height).contains(&y) {
+        return Some(*image.get_pixel(x as u32, y as u32));
+    }
+
+    let (x, y) = match border.mode {
+        BorderMode::Clamp => { (x.clamp(0, width - 1), y.clamp(0, height - 1)) }
+        BorderMode::Mirror => { (mirror(x, width), mirror(y, height)) }
+        BorderMode::Wrap => { (x.rem_euclid(width), y.rem_euclid(height)) }
+        BorderMode::Constant => {
+            let [r, g, b] = border.color.map(|c| c as f32 / u8::MAX as f32);
+            return Some(Rgba([r, g, b, 1.0]))
+        }
+        BorderMode::Skip => { return None }
+    };
+    Some(*image.get_pixel(x as u32, y as u32))
+}
+
+fn mirror(i: i64, len: i64) -> i64 {
+    if len == 1 {
+        return 0;
+    }
+    let period = 2 * (len - 1);
+    let i = i.rem_euclid(period);
+    if i < len { i } else { period - i }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row() -> Rgba32FImage {
+        Rgba32FImage::from_fn(4, 1, |x, _| Rgba([x as f32, 0.0, 0.0, 1.0]))
+    }
+
+    fn red(mode: BorderMode, x: i64) -> Option<f32> {
+        sample(&Border { mode, color: [255, 0, 0] }, &row(), x, 0).map(|p| p[0])
+    }
+
+    #[test]
+    fn modes_pick_the_expected_pixels() {
+        assert_eq!([-2, -1, 4, 5].map(|x| red(BorderMode::Clamp, x)), [Some(0.0), Some(0.0), Some(3.0), Some(3.0)]);
+        assert_eq!([-2, -1, 4, 5].map(|x| red(BorderMode::Mirror, x)), [Some(2.0), Some(1.0), Some(2.0), Some(1.0)]);
+        assert_eq!([-2, -1, 4, 5].map(|x| red(BorderMode::Wrap, x)), [Some(2.0), Some(3.0), Some(0.0), Some(1.0)]);
+        assert_eq!([-1, 4].map(|x| red(BorderMode::Constant, x)), [Some(1.0), Some(1.0)]);
+        assert_eq!([-1, 4].map(|x| red(BorderMode::Skip, x)), [None, None]);
+        // Pixels inside the image are never affected.
+        assert!(BorderMode::ALL.iter().all(|mode| red(*mode, 2) == Some(2.0)));
+    }
+
+    #[test]
+    fn mirror_handles_tiny_images() {
+        let img = Rgba32FImage::new(1, 1);
+        assert!(sample(&Border { mode: BorderMode::Mirror, color: [0; 3] }, &img, -5, 7).is_some());
+        assert_eq!(mirror(-10, 2), 0);
+        assert_eq!(mirror(11, 3), 1);
+    }
+}
@@ -0,0 +1,169 @@
+use std::sync::Arc;
+
+use image::Rgba32FImage;
+
+use crate::models::layer::Layer;
+use crate::services::image::apply_modifier;
+
+/// Memoises the output of every stage of a modifier stack.
+///
+/// Stage `i` is keyed by the layers `0..=i` that produced it, so when a stack is applied only
+/// the stages after the first layer that differs from the previous run are recomputed.
+pub struct StageCache {
+    source: Arc<Rgba32FImage>,
+    stages: Vec<(Layer, Arc<Rgba32FImage>)>
+}
+
+impl StageCache {
+    pub fn new(source: Arc<Rgba32FImage>) -> Self {
+        StageCache {
+            source,
+            stages: vec![],
+        }
+    }
+
+    pub fn source(&self) -> &Arc<Rgba32FImage> {
+        &self.source
+    }
+
+    /// Runs `layers` on the source image, reusing the longest cached prefix.
+    pub fn apply(&mut self, layers: &[Layer]) -> Arc<Rgba32FImage> {
+        self.apply_cancellable(layers, || false).expect("Never cancelled")
+    }
+
+    /// Like [`StageCache::apply`], but checks `cancelled` before every stage and gives up with
+    /// `None` once it returns true. Stages finished before that stay cached.
+    pub fn apply_cancellable(&mut self, layers: &[Layer], cancelled: impl Fn() -> bool) -> Option<Arc<Rgba32FImage>> {
+        let reused = self.stages.iter()
+            .zip(layers)
+            .take_while(|((cached, _), layer)| cached == *layer)
+            .count();
+        self.stages.truncate(reused);
+
+        for layer in &layers[reused..] {
+            if cancelled() {
+                return None;
+            }
+            let output = if layer.enabled {
+                Arc::new(apply_modifier(&layer.modifier, self.output()))
+            } else {
+                self.output().clone()
+            };
+            self.stages.push((layer.clone(), output));
+        }
+
+        Some(self.output().clone())
+    }
+
+    /// Runs `layers` like [`StageCache::apply`], but leaves the cache as it is. Meant for part of
+    /// the stack, which `apply` would cache by dropping the stages after it.
+    pub fn output_of(&self, layers: &[Layer]) -> Arc<Rgba32FImage> {
+        let reused = self.stages.iter()
+            .zip(layers)
+            .take_while(|((cached, _), layer)| cached == *layer)
+            .count();
+
+        let start = if reused == 0 { self.source.clone() } else { self.stages[reused - 1].1.clone() };
+        layers[reused..].iter()
+            .filter(|l| l.enabled)
+            .fold(start, |img, layer| Arc::new(apply_modifier(&layer.modifier, &img)))
+    }
+
+    fn output(&self) -> &Arc<Rgba32FImage> {
+        self.stages.last().map_or(&self.source, |(_, img)| img)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use crate::models::modifier::{BoxBlurOptions, Modifier, NegativeOptions, ThresholdingOptions};
+    use crate::services::image::{apply, from_rgba8};
+
+    use super::*;
+
+    fn source() -> Arc<Rgba32FImage> {
+        Arc::new(from_rgba8(&RgbaImage::from_fn(16, 9, |x, y| Rgba([(x * 15) as u8, (y * 28) as u8, 100, 255]))))
+    }
+
+    #[test]
+    fn matches_uncached_apply() {
+        let modifiers = vec![
+            Layer::new(Modifier::BoxBlur(BoxBlurOptions { size: 3, ..BoxBlurOptions::default() })),
+            Layer::new(Modifier::Negative(NegativeOptions::default())),
+        ];
+        let mut cache = StageCache::new(source());
+        assert_eq!(*cache.apply(&modifiers), apply(&source(), &modifiers));
+        assert_eq!(*cache.apply(&[]), *source());
+    }
+
+    #[test]
+    fn reuses_unchanged_prefix() {
+        let mut modifiers = vec![
+            Layer::new(Modifier::BoxBlur(BoxBlurOptions { size: 3, ..BoxBlurOptions::default() })),
+            Layer::new(Modifier::Negative(NegativeOptions::default())),
+            Layer::new(Modifier::Thresholding(ThresholdingOptions::default())),
+        ];
+        let mut cache = StageCache::new(source());
+        cache.apply(&modifiers);
+        let blurred = cache.stages[0].1.clone();
+        let negated = cache.stages[1].1.clone();
+
+        modifiers[2].modifier = Modifier::Thresholding(ThresholdingOptions { threshold: 10, ..ThresholdingOptions::default() });
+        let output = cache.apply(&modifiers);
+
+        assert!(Arc::ptr_eq(&cache.stages[0].1, &blurred));
+        assert!(Arc::ptr_eq(&cache.stages[1].1, &negated));
+        assert_eq!(*output, apply(&source(), &modifiers));
+    }
+
+    #[test]
+    fn disabled_layers_are_skipped() {
+        let mut modifiers = vec![
+            Layer::new(Modifier::BoxBlur(BoxBlurOptions { size: 3, ..BoxBlurOptions::default() })),
+            Layer::new(Modifier::Negative(NegativeOptions::default())),
+        ];
+        modifiers[1].enabled = false;
+
+        let mut cache = StageCache::new(source());
+        let output = cache.apply(&modifiers);
+        assert!(Arc::ptr_eq(&output, &cache.stages[0].1));
+        assert_eq!(*output, apply(&source(), &modifiers[..1]));
+        assert_eq!(*output, apply(&source(), &modifiers));
+    }
+
+    #[test]
+    fn output_of_keeps_the_cache() {
+        let modifiers = vec![
+            Layer::new(Modifier::BoxBlur(BoxBlurOptions { size: 3, ..BoxBlurOptions::default() })),
+            Layer::new(Modifier::Negative(NegativeOptions::default())),
+        ];
+        let mut cache = StageCache::new(source());
+        cache.apply(&modifiers);
+        let negated = cache.stages[1].1.clone();
+
+        assert!(Arc::ptr_eq(&cache.output_of(&modifiers[..1]), &cache.stages[0].1));
+        assert_eq!(*cache.output_of(&modifiers[1..]), apply(&source(), &modifiers[1..]));
+        assert_eq!(cache.stages.len(), 2);
+        assert!(Arc::ptr_eq(&cache.apply(&modifiers), &negated));
+    }
+
+    #[test]
+    fn cancellation_keeps_finished_stages() {
+        let modifiers = vec![
+            Layer::new(Modifier::BoxBlur(BoxBlurOptions { size: 3, ..BoxBlurOptions::default() })),
+            Layer::new(Modifier::Negative(NegativeOptions::default())),
+        ];
+        let mut cache = StageCache::new(source());
+        let checks = std::cell::Cell::new(0);
+        let output = cache.apply_cancellable(&modifiers, || {
+            checks.set(checks.get() + 1);
+            checks.get() > 1
+        });
+
+        assert!(output.is_none());
+        assert_eq!(cache.stages.len(), 1);
+        assert_eq!(*cache.apply(&modifiers), apply(&source(), &modifiers));
+    }
+}
@@ -0,0 +1,3 @@
+pub fn pitagora(x: f32, y: f32) -> f32 {
+    x.hypot(y)
+}
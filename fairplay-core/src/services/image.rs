@@ -0,0 +1,703 @@
+use image::{Pixel, Rgba, Rgba32FImage, RgbaImage};
+
+use crate::models::histogram::Histogram;
+use crate::models::hue::{ColorModel, HueAdjustment, HueRange};
+use crate::models::layer::Layer;
+use crate::models::border::Border;
+use crate::models::modifier::{BoxBlurOptions, ChannelLevels, ChannelOptions, CurvesOptions, CustomKernelOptions, GaussianBlurOptions, GrayscaleOptions, HueSaturationOptions, LaplaceOptions, LevelsOptions, LightnessCorrectionOptions, MedianBlurOptions, Modifier, NegativeOptions, SharpeningOptions, SobelOptions, ThresholdingOptions, UnsharpMaskingOptions};
+use crate::services::blur;
+use crate::services::border::sample;
+use crate::services::functions::pitagora;
+use crate::services::parallel;
+use crate::services::spline::Spline;
+
+/// Runs the enabled layers on a copy of `image` in the given order.
+///
+/// Channels are floats in `0.0..=1.0`, so stacking modifiers doesn't round to 8 bits between
+/// them. Use [`to_rgba8`] to display the result.
+pub fn apply(image: &Rgba32FImage, layers: &[Layer]) -> Rgba32FImage {
+    let mut img = image.clone();
+    for layer in layers.iter().filter(|l| l.enabled) {
+        img = apply_modifier(&layer.modifier, &img);
+    }
+
+    img
+}
+
+/// Runs a single modifier on `image`.
+pub fn apply_modifier(modifier: &Modifier, image: &Rgba32FImage) -> Rgba32FImage {
+    match modifier {
+        Modifier::Negative(opts) => { negative(opts, image) }
+        Modifier::Thresholding(opts) => { thresholding(opts, image) }
+        Modifier::Grayscale(opts) => { grayscale(opts, image) }
+        Modifier::Channels(opts) => { channels(opts, image) }
+        Modifier::LightnessCorrection(opts) => { lightness_correction(opts, image) }
+        Modifier::BoxBlur(opts) => { box_blur(opts, image) }
+        Modifier::GaussianBlur(opts) => { gaussian_blur(opts, image) },
+        Modifier::MedianBlur(opts) => { median_blur(opts, image) }
+        Modifier::Sobel(opts) => { sobel(opts, image) }
+        Modifier::Laplace(opts) => { laplace(opts, image) }
+        Modifier::Sharpening(opts) => { sharpening(opts, image) }
+        Modifier::UnsharpMasking(opts) => { unsharp_masking(opts, image) }
+        Modifier::CustomKernel(opts) => { custom_kernel(opts, image) }
+        Modifier::Curves(opts) => { curves(opts, image) }
+        Modifier::Levels(opts) => { levels(opts, image) }
+        Modifier::HueSaturation(opts) => { hue_saturation(opts, image) }
+    }
+}
+
+pub fn negative(opts: &NegativeOptions, image: &Rgba32FImage) -> Rgba32FImage {
+    let grayscaled;
+    let img = if opts.grayscale {
+        grayscaled = grayscale(&GrayscaleOptions::default(), image);
+        &grayscaled
+    } else { image };
+
+    parallel::from_fn(img.width(), img.height(), |x, y| {
+        let p = img.get_pixel(x, y);
+        let r = 1.0 - p.channels()[0];
+        let g = 1.0 - p.channels()[1];
+        let b = 1.0 - p.channels()[2];
+        let a = p.channels()[3];
+        Rgba([r, g, b, a])
+    })
+}
+
+pub fn thresholding(opts: &ThresholdingOptions, image: &Rgba32FImage) -> Rgba32FImage {
+    let grayscaled;
+    let img = if opts.grayscale {
+        grayscaled = grayscale(&GrayscaleOptions::default(), image);
+        &grayscaled
+    } else { image };
+
+    let threshold = opts.threshold as f32 / u8::MAX as f32;
+
+    parallel::from_fn(img.width(), img.height(), |x, y| {
+        let p = img.get_pixel(x, y);
+        let r = if threshold > p.channels()[0] { 0.0 } else { 1.0 };
+        let g = if threshold > p.channels()[1] { 0.0 } else { 1.0 };
+        let b = if threshold > p.channels()[2] { 0.0 } else { 1.0 };
+        let a = p.channels()[3];
+        Rgba([r, g, b, a])
+    })
+}
+
+pub fn grayscale(opts: &GrayscaleOptions, image: &Rgba32FImage) -> Rgba32FImage {
+    let sum = opts.red_weight as u16 + opts.green_weight as u16 + opts.blue_weight as u16;
+    let multiplier = 1.0 / sum as f32;
+
+    parallel::from_fn(image.width(), image.height(), |x, y| {
+        let p = image.get_pixel(x, y);
+        let v = opts.red_weight as f32 * multiplier * p.channels()[0] +
+            opts.green_weight as f32 * multiplier * p.channels()[1] +
+            opts.blue_weight as f32 * multiplier * p.channels()[2];
+        let a = p.channels()[3];
+        Rgba([v, v, v, a])
+    })
+}
+
+pub fn channels(opts: &ChannelOptions, image: &Rgba32FImage) -> Rgba32FImage {
+    let weight = |enabled: bool, weight: u8| if enabled { weight as f32 / 100.0 } else { 0.0 };
+    let r_weight = weight(opts.red_enabled, opts.red_weight);
+    let g_weight = weight(opts.green_enabled, opts.green_weight);
+    let b_weight = weight(opts.blue_enabled, opts.blue_weight);
+
+    parallel::from_fn(image.width(), image.height(), |x, y| {
+        let p = image.get_pixel(x, y);
+        let r = (p.channels()[0] * r_weight).min(1.0);
+        let g = (p.channels()[1] * g_weight).min(1.0);
+        let b = (p.channels()[2] * b_weight).min(1.0);
+        let a = p.channels()[3];
+        Rgba([r, g, b, a])
+    })
+}
+
+pub fn lightness_correction(opts: &LightnessCorrectionOptions, image: &Rgba32FImage) -> Rgba32FImage {
+    // The exponent is applied to 0..=255 values, which is what the slider was tuned for.
+    let exp = opts.exponent as f32 / ((u8::MAX as f32) / 2f32);
+    let max = u8::MAX as f32;
+
+    parallel::from_fn(image.width(), image.height(), |x, y| {
+        let p = image.get_pixel(x, y);
+        let r = ((p.channels()[0] * max).powf(exp) / max).min(1.0);
+        let g = ((p.channels()[1] * max).powf(exp) / max).min(1.0);
+        let b = ((p.channels()[2] * max).powf(exp) / max).min(1.0);
+        let a = p.channels()[3];
+        Rgba([r, g, b, a])
+    })
+}
+
+/// Runs every colour channel through the RGB curve and then its own. The luminance curve comes last
+/// and moves all channels by the change of the grey value, with the weights of [`grayscale`].
+pub fn curves(opts: &CurvesOptions, image: &Rgba32FImage) -> Rgba32FImage {
+    let rgb = Spline::new(&opts.rgb);
+    let channels = [Spline::new(&opts.red), Spline::new(&opts.green), Spline::new(&opts.blue)];
+    let luminance = Spline::new(&opts.luminance);
+    let luminance = (!luminance.is_identity()).then_some(luminance);
+
+    parallel::from_fn(image.width(), image.height(), |x, y| {
+        let p = image.get_pixel(x, y);
+        let mut c = [0f32; 3];
+        (0..3).for_each(|i| c[i] = channels[i].eval(rgb.eval(p.channels()[i])));
+        if let Some(luminance) = &luminance {
+            shift_luminance(&mut c, |v| luminance.eval(v));
+        }
+        Rgba([c[0], c[1], c[2], p.channels()[3]])
+    })
+}
+
+/// Levels in the order of [`curves`]: RGB, then every colour channel, then luminance.
+pub fn levels(opts: &LevelsOptions, image: &Rgba32FImage) -> Rgba32FImage {
+    let channels = [opts.red, opts.green, opts.blue];
+    let luminance = (opts.luminance != ChannelLevels::default()).then_some(opts.luminance);
+
+    parallel::from_fn(image.width(), image.height(), |x, y| {
+        let p = image.get_pixel(x, y);
+        let mut c = [0f32; 3];
+        (0..3).for_each(|i| c[i] = level(&channels[i], level(&opts.rgb, p.channels()[i])));
+        if let Some(luminance) = &luminance {
+            shift_luminance(&mut c, |v| level(luminance, v));
+        }
+        Rgba([c[0], c[1], c[2], p.channels()[3]])
+    })
+}
+
+fn level(levels: &ChannelLevels, v: f32) -> f32 {
+    let max = u8::MAX as f32;
+    let (black, white) = (levels.input_black as f32 / max, levels.input_white as f32 / max);
+    let stretched = if white > black { ((v - black) / (white - black)).clamp(0.0, 1.0) } else if v >= black { 1.0 } else { 0.0 };
+    let corrected = stretched.powf(1.0 / levels.gamma.max(0.01));
+    let (low, high) = (levels.output_black as f32 / max, levels.output_white as f32 / max);
+    low + corrected * (high - low)
+}
+
+/// Adjusts hue, saturation and lightness in the colour model of `opts`. The colour ranges add
+/// to the master adjustment, weighted by how close a pixel is to their hue and by its chroma, so
+/// that greys stay untouched.
+pub fn hue_saturation(opts: &HueSaturationOptions, image: &Rgba32FImage) -> Rgba32FImage {
+    let ranges: Vec<(f32, HueAdjustment)> = HueRange::ALL.into_iter()
+        .filter_map(|range| range.centre().map(|centre| (centre, *opts.adjustment(range))))
+        .filter(|(_, adjustment)| *adjustment != HueAdjustment::default())
+        .collect();
+    let master = opts.master;
+
+    parallel::from_fn(image.width(), image.height(), |x, y| {
+        let p = image.get_pixel(x, y);
+        let rgb = [p.channels()[0], p.channels()[1], p.channels()[2]];
+
+        let [mut dh, mut ds, mut dl] = [master.hue as f32, master.saturation as f32, master.lightness as f32];
+        if !opts.colorize && !ranges.is_empty() {
+            let (max, min) = (rgb[0].max(rgb[1]).max(rgb[2]), rgb[0].min(rgb[1]).min(rgb[2]));
+            let hue = hue(rgb, max, max - min);
+            for (centre, adjustment) in &ranges {
+                let distance = ((hue - centre + 180.0).rem_euclid(360.0) - 180.0).abs();
+                let weight = (1.0 - distance / 60.0).max(0.0) * (max - min);
+                dh += adjustment.hue as f32 * weight;
+                ds += adjustment.saturation as f32 * weight;
+                dl += adjustment.lightness as f32 * weight;
+            }
+        }
+
+        let [h, s, l] = match opts.model {
+            ColorModel::Hsl => { rgb_to_hsl(rgb) }
+            ColorModel::Hsv => { rgb_to_hsv(rgb) }
+        };
+        let (h, s) = if opts.colorize {
+            (master.hue as f32, (master.saturation as f32 + 100.0) / 200.0)
+        } else {
+            (h + dh, adjust(s, ds))
+        };
+        let l = adjust(l, dl);
+        let [r, g, b] = match opts.model {
+            ColorModel::Hsl => { hsl_to_rgb([h, s, l]) }
+            ColorModel::Hsv => { hsv_to_rgb([h, s, l]) }
+        };
+
+        Rgba([r.clamp(0.0, 1.0), g.clamp(0.0, 1.0), b.clamp(0.0, 1.0), p.channels()[3]])
+    })
+}
+
+/// Moves `v` towards 0 for negative and towards 1 for positive `amount`s in `-100.0..=100.0`.
+fn adjust(v: f32, amount: f32) -> f32 {
+    let amount = (amount / 100.0).clamp(-1.0, 1.0);
+    if amount < 0.0 { v * (1.0 + amount) } else { v + (1.0 - v) * amount }
+}
+
+/// Hue in degrees `0.0..360.0`, saturation and lightness in `0.0..=1.0`.
+pub fn rgb_to_hsl(rgb: [f32; 3]) -> [f32; 3] {
+    let (max, min) = (rgb[0].max(rgb[1]).max(rgb[2]), rgb[0].min(rgb[1]).min(rgb[2]));
+    let chroma = max - min;
+    let l = (max + min) / 2.0;
+    let s = if chroma == 0.0 { 0.0 } else { (chroma / (1.0 - (2.0 * l - 1.0).abs())).min(1.0) };
+    [hue(rgb, max, chroma), s, l]
+}
+
+/// Inverse of [`rgb_to_hsl`], any hue is accepted.
+pub fn hsl_to_rgb([h, s, l]: [f32; 3]) -> [f32; 3] {
+    let chroma = (1.0 - (2.0 * l - 1.0).abs()) * s;
+    from_hue(h, chroma, l - chroma / 2.0)
+}
+
+/// Hue in degrees `0.0..360.0`, saturation and value in `0.0..=1.0`.
+pub fn rgb_to_hsv(rgb: [f32; 3]) -> [f32; 3] {
+    let (max, min) = (rgb[0].max(rgb[1]).max(rgb[2]), rgb[0].min(rgb[1]).min(rgb[2]));
+    let chroma = max - min;
+    let s = if max == 0.0 { 0.0 } else { chroma / max };
+    [hue(rgb, max, chroma), s, max]
+}
+
+/// Inverse of [`rgb_to_hsv`], any hue is accepted.
+pub fn hsv_to_rgb([h, s, v]: [f32; 3]) -> [f32; 3] {
+    let chroma = v * s;
+    from_hue(h, chroma, v - chroma)
+}
+
+fn hue([r, g, b]: [f32; 3], max: f32, chroma: f32) -> f32 {
+    if chroma == 0.0 {
+        return 0.0;
+    }
+    let sector = if max == r {
+        ((g - b) / chroma).rem_euclid(6.0)
+    } else if max == g {
+        (b - r) / chroma + 2.0
+    } else {
+        (r - g) / chroma + 4.0
+    };
+    sector * 60.0
+}
+
+/// RGB with the given hue and chroma, plus `m` on every channel.
+fn from_hue(h: f32, chroma: f32, m: f32) -> [f32; 3] {
+    let sector = h.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let (r, g, b) = match sector as u32 {
+        0 => { (chroma, x, 0.0) }
+        1 => { (x, chroma, 0.0) }
+        2 => { (0.0, chroma, x) }
+        3 => { (0.0, x, chroma) }
+        4 => { (x, 0.0, chroma) }
+        _ => { (chroma, 0.0, x) }
+    };
+    [r + m, g + m, b + m]
+}
+
+/// Moves all colour channels by the change `f` makes to their grey value, with the weights of
+/// [`grayscale`].
+fn shift_luminance(c: &mut [f32; 3], f: impl Fn(f32) -> f32) {
+    let d = GrayscaleOptions::default();
+    let sum = (d.red_weight as u16 + d.green_weight as u16 + d.blue_weight as u16) as f32;
+    let v = (c[0] * d.red_weight as f32 + c[1] * d.green_weight as f32 + c[2] * d.blue_weight as f32) / sum;
+    let shift = f(v) - v;
+    c.iter_mut().for_each(|c| *c = (*c + shift).clamp(0.0, 1.0));
+}
+
+/// Separable running-sum box blur, the cost per pixel doesn't depend on the size.
+pub fn box_blur(opts: &BoxBlurOptions, image: &Rgba32FImage) -> Rgba32FImage {
+    let radius = (opts.size / 2) as i64;
+    let rows = blur::box_rows(image, radius, &opts.border);
+    blur::transpose(&blur::box_rows(&blur::transpose(&rows), radius, &opts.border))
+}
+
+/// Separable gaussian blur. The 1D weights `e^(-x²/2σ²) / (√(2π)·σ)` multiply to the 2D ones
+/// `e^(-(x²+y²)/2σ²) / (2πσ²)`. They aren't normalised, so small kernels brighten slightly
+/// and the result is clamped.
+pub fn gaussian_blur(opts: &GaussianBlurOptions, image: &Rgba32FImage) -> Rgba32FImage {
+    let o = (opts.size as f32) / 6f32;
+    let radius = (opts.size / 2) as i64;
+
+    let multiplier = 1f32 / ((2f32 * std::f32::consts::PI).sqrt() * o);
+    let kernel: Vec<f32> = (-radius..=radius)
+        .map(|x| multiplier * std::f32::consts::E.powf(-((x.pow(2) as f32) / (2f32 * o.powi(2)))))
+        .collect();
+
+    let rows = blur::convolve_rows(image, &kernel, &opts.border);
+    clamped(&blur::transpose(&blur::convolve_rows(&blur::transpose(&rows), &kernel, &opts.border)))
+}
+
+/// Median filter with a sliding histogram, the cost per pixel grows linearly with the size.
+pub fn median_blur(opts: &MedianBlurOptions, image: &Rgba32FImage) -> Rgba32FImage {
+    blur::median(image, (opts.size / 2) as i64, &opts.border)
+}
+
+pub fn sobel(opts: &SobelOptions, image: &Rgba32FImage) -> Rgba32FImage {
+    let filter_horizontal = [
+        [1.0, 0.0, -1.0].to_vec(),
+        [2.0, 0.0, -2.0].to_vec(),
+        [1.0, 0.0, -1.0].to_vec()
+    ].to_vec();
+    let filter_vertical = [
+        [1.0, 2.0, 1.0].to_vec(),
+        [0.0, 0.0, 0.0].to_vec(),
+        [-1.0, -2.0, -1.0].to_vec()
+    ].to_vec();
+
+    let horizontal_opt = if opts.horizontal {
+        Some(apply_filter(&filter_horizontal, &opts.border, image))
+    } else { None };
+
+    let vertical_opt = if opts.vertical {
+        Some(apply_filter(&filter_vertical, &opts.border, image))
+    } else { None };
+
+    match (horizontal_opt, vertical_opt) {
+        (Some(horizontal), Some(vertical)) => {
+            parallel::from_fn(image.width(), image.height(), |x, y| {
+                let ph = horizontal.get_pixel(x, y);
+                let pv = vertical.get_pixel(x, y);
+
+                let r = pitagora(ph.channels()[0], pv.channels()[0]).min(1.0);
+                let g = pitagora(ph.channels()[1], pv.channels()[1]).min(1.0);
+                let b = pitagora(ph.channels()[2], pv.channels()[2]).min(1.0);
+                let a = ph.channels()[3];
+
+                Rgba([r, g, b, a])
+            })
+        }
+        (Some(filtered), None) | (None, Some(filtered)) => { clamped(&filtered) }
+        (None, None) => { image.clone() }
+    }
+}
+
+pub fn laplace(opts: &LaplaceOptions, image: &Rgba32FImage) -> Rgba32FImage {
+    let filter = [
+        [1.0, 1.0, 1.0].to_vec(),
+        [1.0, -8.0, 1.0].to_vec(),
+        [1.0, 1.0, 1.0].to_vec()
+    ].to_vec();
+
+    clamped(&apply_filter(&filter, &opts.border, image))
+}
+
+pub fn sharpening(opts: &SharpeningOptions, image: &Rgba32FImage) -> Rgba32FImage {
+    let filter = [
+        [1.0, 1.0, 1.0].to_vec(),
+        [1.0, -8.0, 1.0].to_vec(),
+        [1.0, 1.0, 1.0].to_vec()
+    ].to_vec();
+
+    let laplace = apply_filter(&filter, &opts.border, image);
+    parallel::from_fn(image.width(), image.height(), |x, y| {
+        let l = laplace.get_pixel(x, y);
+        let p = image.get_pixel(x, y);
+
+        let r = (p.channels()[0] - l.channels()[0]).clamp(0.0, 1.0);
+        let g = (p.channels()[1] - l.channels()[1]).clamp(0.0, 1.0);
+        let b = (p.channels()[2] - l.channels()[2]).clamp(0.0, 1.0);
+        let a = p.channels()[3];
+
+        Rgba([r, g, b, a])
+    })
+}
+
+pub fn unsharp_masking(opts: &UnsharpMaskingOptions, image: &Rgba32FImage) -> Rgba32FImage {
+    let blur = box_blur(&BoxBlurOptions { size: opts.blur_size, border: opts.border }, image);
+    parallel::from_fn(image.width(), image.height(), |x, y| {
+        let b = blur.get_pixel(x, y);
+        let p = image.get_pixel(x, y);
+
+        let r = (p.channels()[0] * 2.0 - b.channels()[0]).clamp(0.0, 1.0);
+        let g = (p.channels()[1] * 2.0 - b.channels()[1]).clamp(0.0, 1.0);
+        let b = (p.channels()[2] * 2.0 - b.channels()[2]).clamp(0.0, 1.0);
+        let a = p.channels()[3];
+
+        Rgba([r, g, b, a])
+    })
+}
+
+/// Convolves with the kernel of `opts`, divides and adds the bias. Malformed kernels, which can
+/// only come from hand-edited pipelines, leave the image as it is.
+pub fn custom_kernel(opts: &CustomKernelOptions, image: &Rgba32FImage) -> Rgba32FImage {
+    if !opts.is_valid() {
+        return image.clone();
+    }
+
+    let divisor = opts.effective_divisor();
+    let kernel: Vec<Vec<f32>> = opts.kernel.iter().map(|row| row.iter().map(|w| w / divisor).collect()).collect();
+    let bias = opts.bias / u8::MAX as f32;
+
+    let filtered = apply_filter(&kernel, &opts.border, image);
+    parallel::from_fn(image.width(), image.height(), |x, y| {
+        let p = filtered.get_pixel(x, y);
+        let r = (p.channels()[0] + bias).clamp(0.0, 1.0);
+        let g = (p.channels()[1] + bias).clamp(0.0, 1.0);
+        let b = (p.channels()[2] + bias).clamp(0.0, 1.0);
+        let a = p.channels()[3];
+        Rgba([r, g, b, a])
+    })
+}
+
+/// Convolves the colour channels of `image` with `filter`, reading outside of the image as
+/// `border` says. The result isn't clamped, so channels can be negative or above 1.
+pub fn apply_filter(filter: &[Vec<f32>], border: &Border, image: &Rgba32FImage) -> Rgba32FImage {
+    let min = -(filter.len() as i64 / 2);
+    let max = filter.len() as i64 / 2;
+
+    parallel::from_fn(image.width(), image.height(), |x, y| {
+        let centre = image.get_pixel(x, y);
+        let x = x as i64;
+        let y = y as i64;
+
+        let mut rsum = 0f32;
+        let mut gsum = 0f32;
+        let mut bsum = 0f32;
+        for ix in min..=max {
+            for iy in min..=max {
+                let multiplier = filter[(max + iy) as usize][(max + ix) as usize];
+
+                let p = sample(border, image, x + ix, y + iy).unwrap_or(*centre);
+                rsum += p.channels()[0] * multiplier;
+                gsum += p.channels()[1] * multiplier;
+                bsum += p.channels()[2] * multiplier;
+            }
+        }
+
+        Rgba([rsum, gsum, bsum, centre.channels()[3]])
+    })
+}
+
+/// Limits the colour channels of a filter result to `0.0..=1.0`.
+fn clamped(image: &Rgba32FImage) -> Rgba32FImage {
+    parallel::from_fn(image.width(), image.height(), |x, y| {
+        let p = image.get_pixel(x, y);
+        let r = p.channels()[0].clamp(0.0, 1.0);
+        let g = p.channels()[1].clamp(0.0, 1.0);
+        let b = p.channels()[2].clamp(0.0, 1.0);
+        let a = p.channels()[3];
+        Rgba([r, g, b, a])
+    })
+}
+
+/// Rounds `image` to 8 bits per channel, for display.
+pub fn to_rgba8(image: &Rgba32FImage) -> RgbaImage {
+    parallel::from_fn(image.width(), image.height(), |x, y| {
+        Rgba(image.get_pixel(x, y).0.map(|c| (c.clamp(0.0, 1.0) * u8::MAX as f32).round() as u8))
+    })
+}
+
+/// Converts an 8 bit image to the float format [`apply`] works on.
+pub fn from_rgba8(image: &RgbaImage) -> Rgba32FImage {
+    parallel::from_fn(image.width(), image.height(), |x, y| {
+        Rgba(image.get_pixel(x, y).0.map(|c| c as f32 / u8::MAX as f32))
+    })
+}
+
+/// Histogram with 32 buckets per channel, for display.
+pub fn histogram(image: &Rgba32FImage) -> Histogram {
+    histogram_with_buckets(image, 32)
+}
+
+/// Histogram with `buckets` equally wide buckets per channel.
+pub fn histogram_with_buckets(image: &Rgba32FImage, buckets: usize) -> Histogram {
+    let mut lightness = vec![0; buckets];
+    let mut red = vec![0; buckets];
+    let mut green = vec![0; buckets];
+    let mut blue = vec![0; buckets];
+    let bin = |v: f32| ((v.clamp(0.0, 1.0) * buckets as f32) as usize).min(buckets - 1);
+
+    image.pixels().for_each(|p| {
+        let r = p.channels()[0];
+        red[bin(r)] += 1;
+        let g = p.channels()[1];
+        green[bin(g)] += 1;
+        let b = p.channels()[2];
+        blue[bin(b)] += 1;
+        lightness[bin((r + g + b) / 3.0)] += 1;
+    });
+
+    Histogram {
+        lightness,
+        red,
+        green,
+        blue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::border::BorderMode;
+    use crate::models::curve::CurvePoint;
+    use crate::models::hue::{ColorModel, HueAdjustment};
+    use crate::models::kernel::KernelPreset;
+
+    use super::*;
+
+    #[test]
+    fn stacked_modifiers_keep_precision() {
+        let gradient = from_rgba8(&RgbaImage::from_fn(256, 1, |x, _| Rgba([x as u8, 0, 0, 255])));
+        let darken = Modifier::Channels(ChannelOptions { red_weight: 10, ..ChannelOptions::default() });
+        let brighten = Modifier::Channels(ChannelOptions { red_weight: 250, ..ChannelOptions::default() });
+        let output = apply(&gradient, &[Layer::new(darken), Layer::new(brighten)]);
+
+        // Rounding to 8 bits after the first layer would leave only 26 distinct values.
+        let mut reds: Vec<f32> = output.pixels().map(|p| p[0]).collect();
+        reds.dedup();
+        assert_eq!(reds.len(), 256);
+        assert!((output.get_pixel(200, 0)[0] - 200.0 / 255.0 * 0.25).abs() < 1e-5);
+    }
+
+    #[test]
+    fn borders_apply_to_every_neighbourhood_modifier() {
+        let flat = Rgba32FImage::from_pixel(6, 4, Rgba([0.5, 0.5, 0.5, 1.0]));
+        let modifiers = |border: Border| vec![
+            Modifier::BoxBlur(BoxBlurOptions { size: 5, border }),
+            Modifier::GaussianBlur(GaussianBlurOptions { size: 5, border }),
+            Modifier::MedianBlur(MedianBlurOptions { size: 5, border }),
+            Modifier::Sharpening(SharpeningOptions { border }),
+            Modifier::UnsharpMasking(UnsharpMaskingOptions { blur_size: 5, border }),
+        ];
+
+        // A flat image stays flat up to the edges, unless the border brings in another colour.
+        for mode in [BorderMode::Clamp, BorderMode::Mirror, BorderMode::Wrap, BorderMode::Skip] {
+            for modifier in modifiers(Border { mode, color: [0; 3] }) {
+                let output = apply_modifier(&modifier, &flat);
+                let inner = output.get_pixel(3, 2)[0];
+                assert!(output.pixels().all(|p| (p[0] - inner).abs() < 1e-5), "{:?}", modifier);
+            }
+        }
+        for modifier in modifiers(Border { mode: BorderMode::Constant, color: [0; 3] }).into_iter().take(3) {
+            let output = apply_modifier(&modifier, &flat);
+            assert!(output.get_pixel(0, 0)[0] < 0.4, "{:?}", modifier);
+            assert_eq!(output.get_pixel(0, 0)[3], 1.0);
+        }
+    }
+
+    #[test]
+    fn gaussian_blur_keeps_the_original_weights() {
+        let img = Rgba32FImage::from_fn(9, 7, |x, y| Rgba([((x * 5 + y * 3) % 11) as f32 / 10.0, 0.9, 0.3, 1.0]));
+        let opts = GaussianBlurOptions { size: 5, border: Border { mode: BorderMode::Skip, color: [0; 3] } };
+        let blurred = gaussian_blur(&opts, &img);
+
+        // The 2D kernel, with out-of-range pixels replaced by the centre row or column.
+        let o = 5.0 / 6.0;
+        let (width, height) = (img.width() as i64, img.height() as i64);
+        for (x, y, p) in blurred.enumerate_pixels() {
+            let mut sum = [0f32; 3];
+            for iy in -2i64..=2 {
+                for ix in -2i64..=2 {
+                    let weight = (-((ix * ix + iy * iy) as f32) / (2.0 * o * o)).exp() / (2.0 * std::f32::consts::PI * o * o);
+                    let sx = if (0..width).contains(&(x as i64 + ix)) { x as i64 + ix } else { x as i64 };
+                    let sy = if (0..height).contains(&(y as i64 + iy)) { y as i64 + iy } else { y as i64 };
+                    let q = img.get_pixel(sx as u32, sy as u32);
+                    (0..3).for_each(|c| sum[c] += q[c] * weight);
+                }
+            }
+            for c in 0..3 {
+                assert!((p[c] - sum[c].clamp(0.0, 1.0)).abs() < 1e-5, "({}, {}): {:?} != {:?}", x, y, p, sum);
+            }
+        }
+        // At size 3 the weights add up to about 1.03, so a bright image saturates.
+        let bright = gaussian_blur(&GaussianBlurOptions::default(), &Rgba32FImage::from_pixel(5, 5, Rgba([0.99, 0.5, 0.0, 1.0])));
+        assert!(bright.pixels().all(|p| p[0] == 1.0 && p[1] > 0.51));
+    }
+
+    #[test]
+    fn custom_kernels_divide_and_add_the_bias() {
+        let img = from_rgba8(&RgbaImage::from_fn(7, 5, |x, y| Rgba([(x * 30) as u8, (y * 50) as u8, 90, 200])));
+        assert_eq!(custom_kernel(&CustomKernelOptions::default(), &img), img);
+
+        // A normalised kernel of ones is a box blur.
+        let ones = CustomKernelOptions { kernel: vec![vec![1.0; 5]; 5], normalize: true, ..CustomKernelOptions::default() };
+        let blurred = box_blur(&BoxBlurOptions { size: 5, border: Border::default() }, &img);
+        for (a, b) in custom_kernel(&ones, &img).pixels().zip(blurred.pixels()) {
+            (0..4).for_each(|c| assert!((a[c] - b[c]).abs() < 1e-5, "{:?} != {:?}", a, b));
+        }
+
+        // Embossing a flat image leaves only the bias.
+        let flat = Rgba32FImage::from_pixel(4, 4, Rgba([0.3, 0.6, 0.9, 1.0]));
+        let embossed = custom_kernel(&KernelPreset::Emboss.options(), &flat);
+        assert!(embossed.pixels().all(|p| (p[0] - 128.0 / 255.0).abs() < 1e-5 && (p[2] - p[0]).abs() < 1e-5));
+
+        let malformed = CustomKernelOptions { kernel: vec![vec![1.0, 2.0]], ..CustomKernelOptions::default() };
+        assert_eq!(custom_kernel(&malformed, &img), img);
+    }
+
+    #[test]
+    fn curves_adjust_their_channels() {
+        let img = from_rgba8(&RgbaImage::from_fn(16, 16, |x, y| Rgba([(x * 16) as u8, (y * 16) as u8, 128, 255])));
+        let identity = curves(&CurvesOptions::default(), &img);
+        assert!(identity.pixels().zip(img.pixels()).all(|(a, b)| (0..4).all(|c| (a[c] - b[c]).abs() < 1e-6)));
+
+        let inverted = vec![CurvePoint::new(0.0, 1.0), CurvePoint::new(1.0, 0.0)];
+        let red = curves(&CurvesOptions { red: inverted.clone(), ..CurvesOptions::default() }, &img);
+        for (a, b) in red.pixels().zip(img.pixels()) {
+            assert!((a[0] - (1.0 - b[0])).abs() < 1e-6 && (a[1] - b[1]).abs() < 1e-6 && (a[2] - b[2]).abs() < 1e-6);
+        }
+
+        // The red curve runs after the RGB one, so inverting twice gives back red.
+        let both = curves(&CurvesOptions { rgb: inverted.clone(), red: inverted, ..CurvesOptions::default() }, &img);
+        assert!(both.pixels().zip(img.pixels()).all(|(a, b)| (a[0] - b[0]).abs() < 1e-6 && (a[1] - (1.0 - b[1])).abs() < 1e-6));
+
+        let brighter = vec![CurvePoint::new(0.0, 0.2), CurvePoint::new(1.0, 1.0)];
+        let grey = Rgba32FImage::from_pixel(2, 2, Rgba([0.2, 0.4, 0.6, 1.0]));
+        let lifted = curves(&CurvesOptions { luminance: brighter, ..CurvesOptions::default() }, &grey);
+        let shift = lifted.get_pixel(0, 0)[0] - 0.2;
+        assert!(shift > 0.0);
+        assert!((0..3).all(|c| (lifted.get_pixel(1, 1)[c] - grey.get_pixel(1, 1)[c] - shift).abs() < 1e-5));
+    }
+
+    #[test]
+    fn levels_stretch_and_bend_channels() {
+        let img = from_rgba8(&RgbaImage::from_fn(16, 16, |x, y| Rgba([(x * 16) as u8, (y * 16) as u8, 128, 255])));
+        let identity = levels(&LevelsOptions::default(), &img);
+        assert!(identity.pixels().zip(img.pixels()).all(|(a, b)| (0..4).all(|c| (a[c] - b[c]).abs() < 1e-6)));
+
+        let stretch = ChannelLevels { input_black: 64, input_white: 192, ..ChannelLevels::default() };
+        let stretched = levels(&LevelsOptions { red: stretch, ..LevelsOptions::default() }, &img);
+        assert_eq!(stretched.get_pixel(2, 0)[0], 0.0);
+        assert!((stretched.get_pixel(8, 0)[0] - (128.0 - 64.0) / 128.0).abs() < 1e-5);
+        assert_eq!(stretched.get_pixel(13, 0)[0], 1.0);
+        assert!(stretched.pixels().zip(img.pixels()).all(|(a, b)| (a[1] - b[1]).abs() < 1e-6));
+
+        let bent = ChannelLevels { gamma: 2.0, output_black: 51, output_white: 204, ..ChannelLevels::default() };
+        let output = levels(&LevelsOptions { rgb: bent, ..LevelsOptions::default() }, &img);
+        let expected = 0.2 + (128.0f32 / 255.0).sqrt() * 0.6;
+        assert!((output.get_pixel(0, 0)[2] - expected).abs() < 1e-5);
+        assert!((output.get_pixel(0, 0)[0] - 0.2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn hsl_and_hsv_round_trip() {
+        for r in 0..=10 {
+            for g in 0..=10 {
+                for b in 0..=10 {
+                    let rgb = [r as f32 / 10.0, g as f32 / 10.0, b as f32 / 10.0];
+                    for back in [hsl_to_rgb(rgb_to_hsl(rgb)), hsv_to_rgb(rgb_to_hsv(rgb))] {
+                        assert!((0..3).all(|c| (back[c] - rgb[c]).abs() < 1e-5), "{:?} != {:?}", back, rgb);
+                    }
+                }
+            }
+        }
+        assert_eq!(rgb_to_hsl([0.0, 0.0, 1.0]), [240.0, 1.0, 0.5]);
+        assert_eq!(rgb_to_hsv([1.0, 1.0, 0.0]), [60.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn hue_saturation_adjusts_ranges_and_colorizes() {
+        let img = Rgba32FImage::from_fn(3, 1, |x, _| [Rgba([1.0, 0.0, 0.0, 1.0]), Rgba([0.0, 0.0, 1.0, 0.5]), Rgba([0.5, 0.5, 0.5, 1.0])][x as usize]);
+        let close = |a: &Rgba<f32>, b: [f32; 4]| (0..4).all(|c| (a[c] - b[c]).abs() < 1e-5);
+
+        let shifted = hue_saturation(&HueSaturationOptions { master: HueAdjustment { hue: 120, ..HueAdjustment::default() }, ..HueSaturationOptions::default() }, &img);
+        assert!(close(shifted.get_pixel(0, 0), [0.0, 1.0, 0.0, 1.0]));
+        assert!(close(shifted.get_pixel(1, 0), [1.0, 0.0, 0.0, 0.5]));
+
+        // Only the red pixel is in the reds, and the grey one has no colour at all.
+        let reds = HueAdjustment { saturation: -100, lightness: 50, ..HueAdjustment::default() };
+        for model in ColorModel::ALL {
+            let output = hue_saturation(&HueSaturationOptions { model, reds, ..HueSaturationOptions::default() }, &img);
+            let p = output.get_pixel(0, 0);
+            assert!((p[0] - p[1]).abs() < 1e-5 && p[0] > 0.5, "{:?}: {:?}", model, p);
+            assert!(close(output.get_pixel(1, 0), [0.0, 0.0, 1.0, 0.5]));
+            assert!(close(output.get_pixel(2, 0), [0.5, 0.5, 0.5, 1.0]));
+        }
+
+        let colorized = hue_saturation(&HueSaturationOptions { master: HueAdjustment { hue: 120, saturation: 100, ..HueAdjustment::default() }, colorize: true, ..HueSaturationOptions::default() }, &img);
+        assert!(colorized.pixels().all(|p| (rgb_to_hsl([p[0], p[1], p[2]])[0] - 120.0).abs() < 1e-3));
+        assert!(close(colorized.get_pixel(2, 0), [0.0, 1.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn converts_to_and_from_8_bits() {
+        let img = RgbaImage::from_fn(16, 16, |x, y| Rgba([x as u8 * 16, y as u8 * 16, 255, (x * y) as u8]));
+        assert_eq!(to_rgba8(&from_rgba8(&img)), img);
+    }
+}
@@ -0,0 +1,7 @@
+pub mod image;
+pub mod cache;
+pub mod spline;
+mod blur;
+mod border;
+mod functions;
+mod parallel;
@@ -0,0 +1,58 @@
+use image::{ImageBuffer, Pixel};
+use rayon::prelude::*;
+
+/// Parallel counterpart of [`ImageBuffer::from_fn`]: every row is filled on the rayon thread pool.
+///
+/// `f` is called exactly once per pixel, so the result is identical to `ImageBuffer::from_fn`.
+pub fn from_fn<P, F>(width: u32, height: u32, f: F) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel + Send + Sync,
+    P::Subpixel: Send + Sync,
+    F: Fn(u32, u32) -> P + Sync
+{
+    let channels = P::CHANNEL_COUNT as usize;
+    from_rows(width, height, |y, row| {
+        for (x, pixel) in row.chunks_exact_mut(channels).enumerate() {
+            pixel.copy_from_slice(f(x as u32, y).channels());
+        }
+    })
+}
+
+/// Like [`from_fn`], but `f` fills a whole row of subpixels at once, for filters that carry
+/// state from one pixel to the next.
+pub fn from_rows<P, F>(width: u32, height: u32, f: F) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel + Send + Sync,
+    P::Subpixel: Send + Sync,
+    F: Fn(u32, &mut [P::Subpixel]) + Sync
+{
+    let mut buffer = ImageBuffer::new(width, height);
+    let row_len = width as usize * P::CHANNEL_COUNT as usize;
+    if row_len == 0 {
+        return buffer;
+    }
+
+    buffer.par_chunks_mut(row_len).enumerate().for_each(|(y, row)| f(y as u32, row));
+
+    buffer
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use super::*;
+
+    #[test]
+    fn matches_sequential_from_fn() {
+        let f = |x: u32, y: u32| Rgba([(x * 7) as u8, (y * 13) as u8, (x ^ y) as u8, 255]);
+        let expected = RgbaImage::from_fn(131, 67, f);
+        assert_eq!(from_fn(131, 67, f), expected);
+    }
+
+    #[test]
+    fn handles_empty_images() {
+        let img: RgbaImage = from_fn(0, 5, |_, _| Rgba([0, 0, 0, 0]));
+        assert_eq!(img.dimensions(), (0, 5));
+    }
+}
@@ -0,0 +1,137 @@
+use crate::models::curve::CurvePoint;
+
+/// Monotone cubic interpolation through the control points of a curve (Fritsch–Carlson), so the
+/// curve never overshoots between two points. It is flat before the first and after the last one.
+#[derive(Clone, Debug)]
+pub struct Spline {
+    points: Vec<CurvePoint>,
+    tangents: Vec<f32>
+}
+
+impl Spline {
+    /// Points may come in any order. Of points with the same `x` the last one is used, and no
+    /// points give the identity.
+    pub fn new(points: &[CurvePoint]) -> Self {
+        let mut sorted: Vec<CurvePoint> = vec![];
+        for point in points {
+            let point = CurvePoint::new(point.x.clamp(0.0, 1.0), point.y.clamp(0.0, 1.0));
+            match sorted.iter_mut().find(|p| p.x == point.x) {
+                Some(existing) => { *existing = point }
+                None => { sorted.push(point) }
+            }
+        }
+        sorted.sort_by(|a, b| a.x.total_cmp(&b.x));
+        if sorted.is_empty() {
+            sorted = vec![CurvePoint::new(0.0, 0.0), CurvePoint::new(1.0, 1.0)];
+        }
+
+        let tangents = tangents(&sorted);
+        Spline { points: sorted, tangents }
+    }
+
+    pub fn eval(&self, x: f32) -> f32 {
+        let points = &self.points;
+        let last = points.len() - 1;
+        if x <= points[0].x {
+            return points[0].y;
+        }
+        if x >= points[last].x {
+            return points[last].y;
+        }
+
+        let k = points.partition_point(|p| p.x <= x) - 1;
+        let (p0, p1) = (points[k], points[k + 1]);
+        let h = p1.x - p0.x;
+        let t = (x - p0.x) / h;
+        let (t2, t3) = (t * t, t * t * t);
+
+        let y = (2.0 * t3 - 3.0 * t2 + 1.0) * p0.y
+            + (t3 - 2.0 * t2 + t) * h * self.tangents[k]
+            + (-2.0 * t3 + 3.0 * t2) * p1.y
+            + (t3 - t2) * h * self.tangents[k + 1];
+        y.clamp(0.0, 1.0)
+    }
+
+    /// Whether the curve maps every value to itself.
+    pub fn is_identity(&self) -> bool {
+        self.points.iter().all(|p| p.x == p.y) && self.points[0].x == 0.0 && self.points[self.points.len() - 1].x == 1.0
+    }
+}
+
+fn tangents(points: &[CurvePoint]) -> Vec<f32> {
+    let n = points.len();
+    if n < 2 {
+        return vec![0.0; n];
+    }
+
+    let slopes: Vec<f32> = points.windows(2).map(|w| (w[1].y - w[0].y) / (w[1].x - w[0].x)).collect();
+    let mut tangents = vec![0.0; n];
+    tangents[0] = slopes[0];
+    tangents[n - 1] = slopes[n - 2];
+    for k in 1..n - 1 {
+        tangents[k] = if slopes[k - 1] * slopes[k] <= 0.0 { 0.0 } else { (slopes[k - 1] + slopes[k]) / 2.0 };
+    }
+
+    // Limits the tangents so that no segment overshoots.
+    for (k, slope) in slopes.iter().enumerate() {
+        if *slope == 0.0 {
+            tangents[k] = 0.0;
+            tangents[k + 1] = 0.0;
+            continue;
+        }
+        let a = tangents[k] / slope;
+        let b = tangents[k + 1] / slope;
+        let length = a.hypot(b);
+        if length > 3.0 {
+            tangents[k] = 3.0 / length * a * slope;
+            tangents[k + 1] = 3.0 / length * b * slope;
+        }
+    }
+
+    tangents
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(points: &[(f32, f32)]) -> Vec<CurvePoint> {
+        points.iter().map(|(x, y)| CurvePoint::new(*x, *y)).collect()
+    }
+
+    #[test]
+    fn passes_through_the_points() {
+        let points = points(&[(0.0, 0.1), (0.3, 0.5), (0.6, 0.55), (1.0, 0.9)]);
+        let spline = Spline::new(&points);
+        for p in &points {
+            assert!((spline.eval(p.x) - p.y).abs() < 1e-6);
+        }
+        assert!(Spline::new(&[]).is_identity());
+        assert!(!spline.is_identity());
+    }
+
+    #[test]
+    fn stays_monotonic() {
+        let spline = Spline::new(&points(&[(0.0, 0.0), (0.2, 0.6), (0.25, 0.62), (0.8, 0.65), (1.0, 1.0)]));
+        let values: Vec<f32> = (0..=1000).map(|i| spline.eval(i as f32 / 1000.0)).collect();
+        assert!(values.windows(2).all(|w| w[1] >= w[0] - 1e-6));
+
+        // A peak stays a peak instead of overshooting.
+        let peak = Spline::new(&points(&[(0.0, 0.0), (0.5, 0.8), (1.0, 0.0)]));
+        assert!((0..=100).all(|i| peak.eval(i as f32 / 100.0) <= 0.8 + 1e-6));
+    }
+
+    #[test]
+    fn handles_unsorted_and_few_points() {
+        let spline = Spline::new(&points(&[(1.0, 1.0), (0.2, 0.4), (0.0, 0.0), (0.2, 0.3)]));
+        assert!((spline.eval(0.2) - 0.3).abs() < 1e-6);
+
+        let constant = Spline::new(&points(&[(0.4, 0.7)]));
+        assert_eq!(constant.eval(0.0), 0.7);
+        assert_eq!(constant.eval(1.0), 0.7);
+
+        let partial = Spline::new(&points(&[(0.2, 0.1), (0.8, 0.9)]));
+        assert_eq!(partial.eval(0.0), 0.1);
+        assert_eq!(partial.eval(1.0), 0.9);
+    }
+}
@@ -0,0 +1,188 @@
+use std::path::PathBuf;
+
+use fairplay_core::models::layer::Layer;
+use fairplay_core::models::pipeline::{Pipeline, PipelineFormat};
+use image::{DynamicImage, ImageFormat};
+
+use crate::models::export::BitDepth;
+use crate::services;
+
+const USAGE: &str = "\
+Usage: fairplay apply --input <FILE> --output <FILE> [--pipeline <FILE>] [--modifier <MODIFIER>]...
+                     [--bit-depth 8|16]
+
+Runs the modifiers on the input image in the given order and writes the result.
+The output format is derived from the output file extension. Images are written with
+8 bits per channel unless --bit-depth 16 is given, which only PNG and TIFF support.
+
+A pipeline saved from the editor (.json or .ron) can be replayed with --pipeline;
+modifiers given with --modifier are applied after it.
+
+Modifiers are given as <name>[:<option>=<value>[,<option>=<value>]...], e.g.
+  --modifier gaussian-blur:size=5 --modifier thresholding:threshold=120
+
+Available modifiers and their options:
+  negative              grayscale
+  thresholding          grayscale, threshold
+  grayscale             red_weight, green_weight, blue_weight
+  channels              red_enabled, red_weight, green_enabled, green_weight, blue_enabled, blue_weight
+  lightness-correction  exponent
+  curves                rgb, red, green, blue, luminance
+  levels                black, white, gamma, output_black, output_white, also prefixed with
+                        red_, green_, blue_ or luminance_ for a single channel
+  box-blur              size, border, border_color
+  gaussian-blur         size, border, border_color
+  median-blur           size, border, border_color
+  sobel                 horizontal, vertical, border, border_color
+  laplace               border, border_color
+  sharpening            border, border_color
+  unsharp-masking       blur_size, border, border_color
+  hue-saturation        model, colorize, hue, saturation, lightness, also prefixed with reds_,
+                        yellows_, greens_, cyans_, blues_ or magentas_ for a colour range
+  custom-kernel         preset, kernel, divisor, bias, normalize, border, border_color
+
+border sets how pixels outside of the image are read: clamp (default), mirror, wrap,
+constant or skip. border_color is the #rrggbb colour used by constant.
+
+curves takes control points separated by ';' with x and y in 0-1 separated by a space,
+e.g. rgb=0 0;0.25 0.2;0.75 0.8;1 1. The rgb curve runs before the red, green and blue ones.
+
+hue-saturation changes colours in the hsl (default) or hsv model. hue is between -180 and
+180 degrees, saturation and lightness between -100 and 100. colorize=true gives every pixel
+the hue and saturation of the master options.
+
+custom-kernel starts from a preset (emboss, edge-enhance, outline or ridge) or the identity.
+kernel gives rows separated by ';' with weights separated by spaces, e.g. kernel=0 -1 0;-1 4 -1;0 -1 0.
+bias is added in 0-255 steps, normalize=true divides by the sum of the weights.";
+
+struct Args {
+    input: PathBuf,
+    output: PathBuf,
+    modifiers: Vec<Layer>,
+    bit_depth: BitDepth
+}
+
+impl Args {
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut input = None;
+        let mut output = None;
+        let mut pipeline = vec![];
+        let mut modifiers = vec![];
+        let mut bit_depth = BitDepth::Eight;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("Missing value for '{}'", arg));
+            match arg.as_str() {
+                "-i" | "--input" => { input = Some(PathBuf::from(value()?)) }
+                "-o" | "--output" => { output = Some(PathBuf::from(value()?)) }
+                "-p" | "--pipeline" => { pipeline = read_pipeline(&value()?)?.layers() }
+                "-m" | "--modifier" => { modifiers.push(Layer::new(value()?.parse()?)) }
+                "--bit-depth" => {
+                    bit_depth = match value()?.as_str() {
+                        "8" => { BitDepth::Eight }
+                        "16" => { BitDepth::Sixteen }
+                        other => { return Err(format!("Invalid bit depth '{}', expected 8 or 16", other)) }
+                    }
+                }
+                _ => { return Err(format!("Unexpected argument '{}'", arg)) }
+            }
+        }
+
+        let output: PathBuf = output.ok_or("Missing --output")?;
+        if bit_depth == BitDepth::Sixteen && !matches!(ImageFormat::from_path(&output), Ok(ImageFormat::Png | ImageFormat::Tiff)) {
+            return Err(format!("{}: 16 bits per channel need a PNG or TIFF output", output.display()))
+        }
+
+        Ok(Args {
+            input: input.ok_or("Missing --input")?,
+            output,
+            modifiers: pipeline.into_iter().chain(modifiers).collect(),
+            bit_depth,
+        })
+    }
+}
+
+fn read_pipeline(path: &str) -> Result<Pipeline, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    Pipeline::deserialize(&contents, PipelineFormat::from_file_name(path))
+        .map_err(|e| format!("Failed to load {}: {}", path, e))
+}
+
+/// Runs the `fairplay apply` subcommand and returns the process exit code.
+pub fn run(args: impl IntoIterator<Item = String>) -> i32 {
+    let args: Vec<String> = args.into_iter().collect();
+    if args.iter().any(|a| a == "-h" || a == "--help") {
+        println!("{}", USAGE);
+        return 0;
+    }
+
+    let result = Args::parse(args).and_then(|args| apply(&args));
+    if let Err(e) = result {
+        eprintln!("error: {}\n\n{}", e, USAGE);
+        return 1;
+    }
+    0
+}
+
+fn apply(args: &Args) -> Result<(), String> {
+    let data = std::fs::read(&args.input)
+        .map_err(|e| format!("Failed to read {}: {}", args.input.display(), e))?;
+    let (img, _) = services::file::decode(&args.input.to_string_lossy(), data)
+        .map_err(|e| format!("{}: {}", args.input.display(), e))?;
+
+    let img = DynamicImage::ImageRgba32F(fairplay_core::apply(&img, &args.modifiers));
+    let img = match args.bit_depth {
+        BitDepth::Eight => { DynamicImage::ImageRgba8(img.into_rgba8()) }
+        BitDepth::Sixteen => { DynamicImage::ImageRgba16(img.into_rgba16()) }
+    };
+
+    img.save(&args.output)
+        .map_err(|e| format!("Failed to write {}: {}", args.output.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use fairplay_core::models::modifier::{BoxBlurOptions, Modifier, NegativeOptions};
+
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn input_and_output_are_required() {
+        assert_eq!(parse(&["--output", "out.png"]).err().as_deref(), Some("Missing --input"));
+        assert_eq!(parse(&["--input", "in.png"]).err().as_deref(), Some("Missing --output"));
+        assert_eq!(parse(&["--input"]).err().as_deref(), Some("Missing value for '--input'"));
+
+        let args = parse(&["-i", "in.png", "-o", "out.png"]).unwrap();
+        assert_eq!((args.input, args.output), (PathBuf::from("in.png"), PathBuf::from("out.png")));
+        assert!(args.modifiers.is_empty());
+    }
+
+    #[test]
+    fn modifiers_follow_the_pipeline() {
+        let path = std::env::temp_dir().join(format!("fairplay-cli-{}.json", std::process::id()));
+        let blur = Modifier::BoxBlur(BoxBlurOptions { size: 5, ..BoxBlurOptions::default() });
+        std::fs::write(&path, Pipeline::new(&[Layer::new(blur.clone())]).to_json()).unwrap();
+
+        let args = parse(&["--input", "in.png", "--output", "out.png", "--modifier", "negative", "--pipeline", path.to_str().unwrap()]);
+        std::fs::remove_file(&path).unwrap();
+        let modifiers: Vec<Modifier> = args.unwrap().modifiers.into_iter().map(|l| l.modifier).collect();
+        assert_eq!(modifiers, vec![blur, Modifier::Negative(NegativeOptions::default())]);
+    }
+
+    #[test]
+    fn sixteen_bits_need_png_or_tiff() {
+        let jpeg = parse(&["--input", "in.png", "--output", "out.jpg", "--bit-depth", "16"]);
+        assert_eq!(jpeg.err().as_deref(), Some("out.jpg: 16 bits per channel need a PNG or TIFF output"));
+        for output in ["out.png", "out.tiff"] {
+            assert!(parse(&["--input", "in.png", "--output", output, "--bit-depth", "16"]).is_ok());
+        }
+        assert!(parse(&["--input", "in.png", "--output", "out.jpg", "--bit-depth", "8"]).is_ok());
+        assert!(parse(&["--input", "in.png", "--output", "out.png", "--bit-depth", "12"]).is_err());
+    }
+}
@@ -1,12 +1,22 @@
+use std::sync::Arc;
+
+use fairplay_core::models::curve::CurveChannel;
+use fairplay_core::models::histogram::Histogram;
+use fairplay_core::models::hue::HueRange;
+use fairplay_core::models::modifier::Modifier;
 use iced::{Application, Command, Element, executor, font, Theme};
-use image::{ImageBuffer, Rgba, RgbaImage};
+use image::Rgba32FImage;
 
 use crate::{update, view};
 use crate::interface::editing::EditingView;
-use crate::interface::histogram::Histogram;
+use crate::interface::editing_components::KernelField;
 use crate::interface::home::HomeView;
-use crate::models::modifier::Modifier;
+use crate::models::export::ExportSettings;
+use crate::models::metadata::Metadata;
+use crate::models::project::Project;
+use crate::services::file::FileError;
 
+#[allow(clippy::large_enum_variant)]
 pub enum Fairplay {
     Home(HomeView),
     Editing(EditingView)
@@ -16,19 +26,60 @@ pub enum Fairplay {
 pub enum Message {
     Started,
     OpenPicker,
-    Open(RgbaImage),
-    ImageModified(ImageBuffer<Rgba<u8>, Vec<u8>>),
+    Open(Rgba32FImage, Metadata),
+    OpenProjectPicker,
+    ProjectOpened(Project),
+    ImageModified(u64, Option<Arc<Rgba32FImage>>),
+    PreviewRendered(u64, Option<Arc<Rgba32FImage>>),
     ModifierAdded(Modifier),
     ModifierRemoved(usize),
+    ModifierMoved(usize, usize),
+    ModifierToggled(usize),
     ModifierOptionsChanged(Modifier),
+    KernelValueEdited(KernelField, String),
+    ChannelSelected(CurveChannel),
+    LevelsAuto,
+    LevelsAutoMeasured(u64, usize, Histogram),
+    CurvesInputMeasured(u64, usize, Histogram),
+    LevelsClipChanged(f32),
+    HueRangeSelected(HueRange),
     ModifierOptionsApplied,
     ModifierSelected(usize, Modifier),
+    SnapshotNameChanged(String),
+    SnapshotPinned,
+    SnapshotRestored(u64),
+    SnapshotRemoved(u64),
+    SnapshotCompared(u64),
+    ComparisonRendered(u64, Arc<Rgba32FImage>),
     Undo,
     Redo,
+    HistoryStateSelected(usize),
+    HistoryStateCompared(usize),
     Save,
+    ToggleExport,
+    ExportSettingsChanged(ExportSettings),
+    SavePipeline,
+    SaveProject,
     Saved,
+    Cancelled,
+    Failed(Arc<FileError>),
+    NotificationDismissed,
     HistogramRecalculated(Histogram),
-    ToggleHistograms
+    ToggleHistograms,
+    ToggleHistory,
+    ToggleInfo
+}
+
+impl Message {
+    /// Turns the outcome of a file dialog into a message: `f` for a picked file,
+    /// `Cancelled` when the dialog was dismissed and `Failed` on errors.
+    pub fn from_file<T>(result: Result<Option<T>, FileError>, f: impl FnOnce(T) -> Message) -> Message {
+        match result {
+            Ok(Some(value)) => { f(value) }
+            Ok(None) => { Message::Cancelled }
+            Err(e) => { Message::Failed(Arc::new(e)) }
+        }
+    }
 }
 
 impl Application for Fairplay {
@@ -49,7 +100,7 @@ impl Application for Fairplay {
         update::update(self, message)
     }
 
-    fn view(&self) -> Element<Self::Message> {
+    fn view(&self) -> Element<'_, Self::Message> {
         view::view(self)
     }
 
@@ -7,6 +7,9 @@ use iced_aw::{floating_element, Spinner};
 #[cfg(not(target_arch = "wasm32"))]
 use iced::widget::{Container, container};
 use iced::{alignment, Background, Length};
+use iced::widget::Button;
+use iced_aw::{BOOTSTRAP_FONT, BootstrapIcon};
+use iced_aw::graphics::icons::icon_to_char;
 use crate::fairplay::Message;
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -45,6 +48,26 @@ pub fn ranged_named_slider<'a>(name: &'a str, range: RangeInclusive<u8>, step: u
         .spacing(10)
         .into()
 }
+/// A dismissable error bar.
+pub fn notification<'a>(text: String) -> Element<'a, Message> {
+    iced::widget::Container::new(
+        Row::new()
+            .push(Text::new(text).width(Length::Fill))
+            .push(
+                Button::new(Text::new(String::from(icon_to_char(BootstrapIcon::X))).font(BOOTSTRAP_FONT))
+                    .on_press(Message::NotificationDismissed)
+                    .style(iced::theme::Button::Custom(Box::new(TransparentButtonStyle)))
+            )
+            .align_items(alignment::Alignment::Center)
+            .padding([5, 10])
+    )
+        .width(Length::Fill)
+        .style(iced::widget::container::Appearance::default()
+            .with_background(Background::from(Color::new(0.6, 0.1, 0.1, 1.0)))
+        )
+        .into()
+}
+
 #[derive(Default)]
 pub struct TransparentButtonStyle;
 
@@ -0,0 +1,179 @@
+use fairplay_core::models::curve::CurvePoint;
+use fairplay_core::services::spline::Spline;
+use iced::{Color, mouse, Point, Rectangle, Renderer, Size, Theme};
+use iced::event::Status;
+use iced::mouse::Cursor;
+use iced::widget::{canvas, Canvas};
+use iced::widget::canvas::{Event, Frame, Geometry, Path, Stroke};
+
+/// Space around the curve, so that points on the edges can be grabbed.
+const MARGIN: f32 = 6.0;
+/// Distance in pixels within which a point is grabbed.
+const GRAB_DISTANCE: f32 = 8.0;
+/// Horizontal gap kept between neighbouring points.
+const MIN_GAP: f32 = 0.01;
+
+/// Curve editor with `histogram` drawn behind it. Clicking adds a point, dragging moves it and a
+/// right click removes it.
+pub fn curve_editor<'a, Message>(points: &[CurvePoint], histogram: Vec<u32>, color: Color, on_change: impl Fn(Vec<CurvePoint>) -> Message + 'a) -> Canvas<CurveProgram<'a, Message>, Message> {
+    canvas(CurveProgram::new(points, histogram, color, on_change))
+}
+
+pub struct CurveProgram<'a, Message> {
+    points: Vec<CurvePoint>,
+    histogram: Vec<u32>,
+    color: Color,
+    on_change: Box<dyn Fn(Vec<CurvePoint>) -> Message + 'a>
+}
+
+impl<'a, Message> CurveProgram<'a, Message> {
+    pub fn new(points: &[CurvePoint], histogram: Vec<u32>, color: Color, on_change: impl Fn(Vec<CurvePoint>) -> Message + 'a) -> Self {
+        let mut points = points.to_vec();
+        points.sort_by(|a, b| a.x.total_cmp(&b.x));
+        CurveProgram {
+            points,
+            histogram,
+            color,
+            on_change: Box::new(on_change),
+        }
+    }
+
+    /// Index of the point within grabbing distance of `position`.
+    fn grabbed(&self, bounds: Rectangle, position: Point) -> Option<usize> {
+        self.points.iter()
+            .map(|p| to_screen(bounds, *p).distance(position))
+            .enumerate()
+            .filter(|(_, distance)| *distance <= GRAB_DISTANCE)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(i, _)| i)
+    }
+
+    /// The points with point `idx` moved to `position`, kept between its neighbours.
+    fn moved(&self, bounds: Rectangle, idx: usize, position: Point) -> Vec<CurvePoint> {
+        let mut point = from_screen(bounds, position);
+        let min = if idx == 0 { 0.0 } else { self.points[idx - 1].x + MIN_GAP };
+        let max = self.points.get(idx + 1).map(|p| p.x - MIN_GAP).unwrap_or(1.0);
+        point.x = point.x.clamp(min, max.max(min));
+
+        let mut points = self.points.clone();
+        points[idx] = point;
+        points
+    }
+}
+
+impl<'a, Message> canvas::Program<Message> for CurveProgram<'a, Message> {
+    /// The point being dragged.
+    type State = Option<usize>;
+
+    fn update(&self, state: &mut Self::State, event: Event, bounds: Rectangle, cursor: Cursor) -> (Status, Option<Message>) {
+        let Event::Mouse(event) = event else { return (Status::Ignored, None) };
+        match event {
+            mouse::Event::ButtonPressed(mouse::Button::Left) => {
+                let Some(position) = cursor.position_in(bounds) else { return (Status::Ignored, None) };
+                if let Some(idx) = self.grabbed(bounds, position) {
+                    *state = Some(idx);
+                    return (Status::Captured, None);
+                }
+
+                let point = from_screen(bounds, position);
+                let idx = self.points.partition_point(|p| p.x < point.x);
+                let mut points = self.points.clone();
+                points.insert(idx, point);
+                *state = Some(idx);
+                (Status::Captured, Some((self.on_change)(points)))
+            }
+            mouse::Event::CursorMoved { position } => {
+                let Some(idx) = *state else { return (Status::Ignored, None) };
+                if idx >= self.points.len() {
+                    *state = None;
+                    return (Status::Ignored, None);
+                }
+                let position = Point::new(position.x - bounds.x, position.y - bounds.y);
+                (Status::Captured, Some((self.on_change)(self.moved(bounds, idx, position))))
+            }
+            mouse::Event::ButtonReleased(mouse::Button::Left) if state.is_some() => {
+                *state = None;
+                (Status::Captured, None)
+            }
+            mouse::Event::ButtonPressed(mouse::Button::Right) => {
+                let Some(position) = cursor.position_in(bounds) else { return (Status::Ignored, None) };
+                match self.grabbed(bounds, position) {
+                    Some(idx) if self.points.len() > 2 => {
+                        let mut points = self.points.clone();
+                        points.remove(idx);
+                        (Status::Captured, Some((self.on_change)(points)))
+                    }
+                    _ => { (Status::Ignored, None) }
+                }
+            }
+            _ => { (Status::Ignored, None) }
+        }
+    }
+
+    fn draw(&self, _state: &Self::State, renderer: &Renderer, _theme: &Theme, bounds: Rectangle, _cursor: Cursor) -> Vec<Geometry> {
+        let mut frame = Frame::new(renderer, bounds.size());
+        let (width, height) = (bounds.width - 2.0 * MARGIN, bounds.height - 2.0 * MARGIN);
+
+        if let Some(max_val) = self.histogram.iter().max().filter(|max| **max > 0) {
+            let bar_width = width / self.histogram.len() as f32;
+            let color = Color { a: 0.25, ..self.color };
+            for (idx, val) in self.histogram.iter().enumerate() {
+                let bar_height = *val as f32 / *max_val as f32 * height;
+                let top_left = Point::new(MARGIN + bar_width * idx as f32, MARGIN + height - bar_height);
+                frame.fill_rectangle(top_left, Size::new(bar_width, bar_height), color);
+            }
+        }
+
+        let guide = Stroke::default().with_color(Color::from_rgba(0.5, 0.5, 0.5, 0.4)).with_width(1.0);
+        for i in 1..4 {
+            let offset = i as f32 / 4.0;
+            frame.stroke(&Path::line(Point::new(MARGIN + width * offset, MARGIN), Point::new(MARGIN + width * offset, MARGIN + height)), guide.clone());
+            frame.stroke(&Path::line(Point::new(MARGIN, MARGIN + height * offset), Point::new(MARGIN + width, MARGIN + height * offset)), guide.clone());
+        }
+        frame.stroke(&Path::line(to_screen(bounds, CurvePoint::new(0.0, 0.0)), to_screen(bounds, CurvePoint::new(1.0, 1.0))), guide);
+
+        let spline = Spline::new(&self.points);
+        let steps = width.max(1.0) as usize;
+        let curve = Path::new(|builder| {
+            builder.move_to(to_screen(bounds, CurvePoint::new(0.0, spline.eval(0.0))));
+            for step in 1..=steps {
+                let x = step as f32 / steps as f32;
+                builder.line_to(to_screen(bounds, CurvePoint::new(x, spline.eval(x))));
+            }
+        });
+        frame.stroke(&curve, Stroke::default().with_color(self.color).with_width(2.0));
+
+        for point in &self.points {
+            frame.fill(&Path::circle(to_screen(bounds, *point), 4.0), self.color);
+        }
+
+        vec![frame.into_geometry()]
+    }
+
+    fn mouse_interaction(&self, state: &Self::State, bounds: Rectangle, cursor: Cursor) -> mouse::Interaction {
+        let over_point = cursor.position_in(bounds).and_then(|position| self.grabbed(bounds, position)).is_some();
+        if state.is_some() {
+            mouse::Interaction::Grabbing
+        } else if over_point {
+            mouse::Interaction::Grab
+        } else if cursor.is_over(bounds) {
+            mouse::Interaction::Crosshair
+        } else {
+            mouse::Interaction::default()
+        }
+    }
+}
+
+/// Position of `point` relative to the canvas.
+fn to_screen(bounds: Rectangle, point: CurvePoint) -> Point {
+    let (width, height) = (bounds.width - 2.0 * MARGIN, bounds.height - 2.0 * MARGIN);
+    Point::new(MARGIN + point.x * width, MARGIN + (1.0 - point.y) * height)
+}
+
+/// Curve coordinates of a position relative to the canvas, limited to the curve area.
+fn from_screen(bounds: Rectangle, position: Point) -> CurvePoint {
+    let (width, height) = (bounds.width - 2.0 * MARGIN, bounds.height - 2.0 * MARGIN);
+    let x = ((position.x - MARGIN) / width).clamp(0.0, 1.0);
+    let y = (1.0 - (position.y - MARGIN) / height).clamp(0.0, 1.0);
+    CurvePoint::new(x, y)
+}
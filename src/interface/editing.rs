@@ -1,53 +1,255 @@
-use std::io::Cursor;
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
 
+use fairplay_core::models::histogram::Histogram;
+use fairplay_core::models::layer::Layer;
+use fairplay_core::models::modifier::{BoxBlurOptions, ChannelOptions, CurvesOptions, CustomKernelOptions, GaussianBlurOptions, GrayscaleOptions, HueSaturationOptions, LaplaceOptions, LevelsOptions, LightnessCorrectionOptions, MedianBlurOptions, Modifier, NegativeOptions, SharpeningOptions, SobelOptions, ThresholdingOptions, UnsharpMaskingOptions};
+use fairplay_core::models::pipeline::Pipeline;
+use fairplay_core::StageCache;
 use iced::{Alignment, Background, Color, Command, Element, Length};
-use iced::widget::{Button, button, Column, Container, container, pick_list, Row, Space, Text};
+use iced::widget::{Button, button, Column, Container, container, pick_list, Row, Scrollable, Space, Text, text_input};
 use iced::widget::image::Handle as ImageHandle;
 use iced_aw::{BOOTSTRAP_FONT, BootstrapIcon};
 use iced_aw::graphics::icons::icon_to_char;
-use image::{ImageFormat, RgbaImage};
-use image::io::Reader as ImageReader;
-use once_cell::sync::Lazy;
-use rfd::AsyncFileDialog;
-use undo::Record;
+use image::Rgba32FImage;
 
 use crate::fairplay::{Fairplay, Message};
-use crate::interface::components::{SelectedButtonStyle, TransparentButtonStyle, with_spinner};
-use crate::interface::editing_components::modifier_options;
-use crate::interface::histogram::{histogram, Histogram};
+use crate::interface::components::{notification, SelectedButtonStyle, TransparentButtonStyle, with_spinner};
+use crate::interface::editing_components::{EditorState, modifier_options};
+use crate::interface::export::export_options;
+use crate::interface::histogram::histogram;
 use crate::interface::View;
-use crate::models::history::{Action, ModifierAdded, ModifierOptionsApplied, ModifierRemoved, ModifierSelected};
-use crate::models::modifier::{BoxBlurOptions, ChannelOptions, GaussianBlurOptions, GrayscaleOptions, LightnessCorrectionOptions, MedianBlurOptions, Modifier, NegativeOptions, SobelOptions, ThresholdingOptions, UnsharpMaskingOptions};
+use crate::models::export::ExportSettings;
+use crate::models::history::{Action, History, ModifierAdded, ModifierMoved, ModifierOptionsApplied, ModifierRemoved, ModifierSelected, ModifierToggled, SnapshotRestored};
+use crate::models::metadata::Metadata;
+use crate::models::project::Project;
+use crate::models::snapshot::Snapshot;
 use crate::services;
 
-static RECORD: Lazy<Mutex<Record<Action>>> = Lazy::new(|| {
-    Mutex::new(Record::new())
-});
+/// Source of render generations and snapshot ids. They are unique across documents, so results
+/// that arrive after a document has been replaced never match the open one.
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+fn next_id() -> u64 {
+    NEXT_ID.fetch_add(1, Ordering::SeqCst)
+}
 
 pub struct EditingView {
-    pub(crate) image: Arc<RgbaImage>,
+    /// Tags work started for this document, such as auto levels measurements.
+    pub(crate) document: u64,
+    pub(crate) image: Arc<Rgba32FImage>,
+    pub(crate) cache: Arc<Mutex<StageCache>>,
+    pub(crate) proxy_cache: Arc<Mutex<StageCache>>,
+    pub(crate) generation: Arc<AtomicU64>,
     pub(crate) handle: ImageHandle,
 
     pub(crate) loading: bool,
-    pub(crate) modifiers: Vec<Modifier>,
+    pub(crate) notification: Option<String>,
+    pub(crate) modifiers: Vec<Layer>,
     pub(crate) selected_modifier: Option<(usize, Modifier)>,
+    pub(crate) editor: EditorState,
 
     pub(crate) histogram_data: Histogram,
-    pub(crate) histogram_visible: bool
+    pub(crate) histogram_visible: bool,
+
+    pub(crate) history_visible: bool,
+    pub(crate) export_visible: bool,
+    pub(crate) export_settings: ExportSettings,
+    pub(crate) metadata: Metadata,
+    pub(crate) info_visible: bool,
+    history: History<Action>,
+
+    pub(crate) snapshots: Vec<Snapshot>,
+    pub(crate) snapshot_name: String,
+    next_snapshot_number: usize,
+    /// Snapshots and history states shown side by side.
+    pub(crate) compared: Vec<Comparison>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ComparisonSource {
+    Snapshot(u64),
+    History(usize)
+}
+
+#[derive(Debug)]
+pub(crate) struct Comparison {
+    pub(crate) source: ComparisonSource,
+    pub(crate) name: String,
+    /// Tags the render so that it finds its way back here.
+    pub(crate) id: u64,
+    pub(crate) handle: Option<ImageHandle>
 }
 
 impl EditingView {
-    pub fn new(img: RgbaImage) -> Self {
+    pub fn new(img: Rgba32FImage) -> Self {
+        let image = Arc::new(img);
         EditingView {
-            handle: ImageHandle::from_pixels(img.width(), img.height(), img.to_vec()),
-            image: Arc::new(img),
+            document: next_id(),
+            handle: display_handle(&image),
+            cache: Arc::new(Mutex::new(StageCache::new(image.clone()))),
+            proxy_cache: Arc::new(Mutex::new(StageCache::new(services::image::proxy(&image)))),
+            generation: Arc::new(AtomicU64::new(0)),
+            image,
             loading: false,
+            notification: None,
             modifiers: vec![],
             selected_modifier: None,
+            editor: EditorState::default(),
             histogram_data: Histogram::default(),
             histogram_visible: false,
+            history_visible: false,
+            export_visible: false,
+            export_settings: services::file::load_export_settings(),
+            metadata: Metadata::default(),
+            info_visible: false,
+            history: History::new(),
+            snapshots: vec![],
+            snapshot_name: String::new(),
+            next_snapshot_number: 1,
+            compared: vec![],
+        }
+    }
+
+    pub fn from_project(project: Project) -> Self {
+        let mut view = EditingView::new(project.image);
+        view.selected_modifier = project.selected_modifier.map(|i| (i, project.modifiers[i].modifier.clone()));
+        view.modifiers = project.modifiers;
+        view.histogram_visible = project.histogram_visible;
+        view.metadata = project.metadata;
+        view.loading = true;
+        view
+    }
+
+    pub fn open_project_picker() -> Command<Message> {
+        Command::perform(services::file::open_project(), |result| Message::from_file(result, Message::ProjectOpened))
+    }
+
+    pub fn open_project(app: &mut Fairplay, project: Project) -> Command<Message> {
+        if let Fairplay::Editing(previous) = app {
+            previous.close();
+        }
+        let view = EditingView::from_project(project);
+        let command = view.render();
+        *app = Fairplay::Editing(view);
+        command
+    }
+
+    /// Re-renders the modifier stack, reusing cached stages where the stack is unchanged.
+    ///
+    /// Every render gets a new generation; older renders that are still running stop at the next
+    /// stage and their results are dropped in `Message::ImageModified`. Generations are unique
+    /// across documents, so renders of a replaced document are dropped too.
+    ///
+    /// Options of the selected modifier that haven't been applied yet are rendered too, so the
+    /// picture keeps matching the options panel. Exports, saved files and snapshots take the
+    /// same stack, so they match the picture.
+    fn render(&self) -> Command<Message> {
+        let generation = next_id();
+        self.generation.store(generation, Ordering::SeqCst);
+        let latest = self.generation.clone();
+        Command::perform(
+            services::image::render(self.cache.clone(), self.previewed_modifiers(), move || latest.load(Ordering::SeqCst) != generation),
+            move |image| Message::ImageModified(generation, image)
+        )
+    }
+
+    /// Renders the stack on the downscaled proxy image, for immediate feedback while options
+    /// are being changed. Once the preview is shown a full resolution render follows.
+    fn render_preview(&self) -> Command<Message> {
+        let generation = next_id();
+        self.generation.store(generation, Ordering::SeqCst);
+        let latest = self.generation.clone();
+        Command::perform(
+            services::image::render(self.proxy_cache.clone(), self.proxy_layers(&self.previewed_modifiers()), move || latest.load(Ordering::SeqCst) != generation),
+            move |image| Message::PreviewRendered(generation, image)
+        )
+    }
+
+    /// `layers` with their sizes scaled to the proxy image.
+    fn proxy_layers(&self, layers: &[Layer]) -> Vec<Layer> {
+        let scale = self.proxy_cache.lock().unwrap().source().width() as f32 / self.image.width() as f32;
+        services::image::proxy_layers(layers, scale)
+    }
+
+    /// Measures what goes into the selected curves modifier, for the curve editor to draw.
+    fn measure_curves_input(&self) -> Command<Message> {
+        let Some((idx, Modifier::Curves(_))) = &self.selected_modifier else { return Command::none() };
+        let (idx, document) = (*idx, self.document);
+        Command::perform(
+            services::image::output_histogram(self.proxy_cache.clone(), self.proxy_layers(&self.modifiers[..idx])),
+            move |histogram| Message::CurvesInputMeasured(document, idx, histogram)
+        )
+    }
+
+    /// Stops the renders that are still running, before the document is replaced by another one.
+    fn close(&self) {
+        self.generation.store(next_id(), Ordering::SeqCst);
+    }
+
+    /// Applies `action` to this document and records it in the document's history.
+    fn apply(&mut self, action: Action) {
+        let mut history = std::mem::take(&mut self.history);
+        history.apply(self, action);
+        self.history = history;
+    }
+
+    fn undo(&mut self) {
+        let mut history = std::mem::take(&mut self.history);
+        history.undo(self);
+        self.history = history;
+    }
+
+    fn redo(&mut self) {
+        let mut history = std::mem::take(&mut self.history);
+        history.redo(self);
+        self.history = history;
+    }
+
+    fn go_to(&mut self, state: usize) {
+        let mut history = std::mem::take(&mut self.history);
+        history.go_to(self, state);
+        self.history = history;
+    }
+
+    /// The modifier stack with the pending options of the selected modifier in place. This is what
+    /// is shown, exported and saved.
+    fn previewed_modifiers(&self) -> Vec<Layer> {
+        let mut modifiers = self.modifiers.clone();
+        if let Some((idx, modifier)) = &self.selected_modifier {
+            modifiers[*idx].modifier = modifier.clone();
+        }
+        modifiers
+    }
+
+    /// The modifier stack of the history state `id`, leaving the current state untouched.
+    fn history_stack(&mut self, id: usize) -> Vec<Layer> {
+        let selected_modifier = self.selected_modifier.clone();
+        let mut history = std::mem::take(&mut self.history);
+        let modifiers = history.inspect(self, id, |state| state.modifiers.clone());
+        self.history = history;
+        self.selected_modifier = selected_modifier;
+        modifiers
+    }
+
+    /// Toggles `source` in the side by side view, rendering `modifiers` for it. At most two are
+    /// shown, the oldest making room.
+    fn compare(&mut self, source: ComparisonSource, name: String, modifiers: Vec<Layer>) -> Command<Message> {
+        if self.compared.iter().any(|c| c.source == source) {
+            self.compared.retain(|c| c.source != source);
+            return Command::none();
         }
+        if self.compared.len() == 2 {
+            self.compared.remove(0);
+        }
+        let id = next_id();
+        self.compared.push(Comparison { source, name, id, handle: None });
+
+        let cache = Arc::new(Mutex::new(StageCache::new(self.image.clone())));
+        Command::perform(
+            services::image::apply(cache, modifiers),
+            move |image| Message::ComparisonRendered(id, image)
+        )
     }
 }
 
@@ -57,105 +259,245 @@ impl View for EditingView {
         match message {
             Message::ModifierAdded(modifier) => {
                 state.loading = true;
-                let r = RECORD.lock();
-                if let Ok(mut rrr) = r {
-                    rrr.apply(state, Action::ModifierAdded(ModifierAdded::new(modifier)));
-                } else {
-                    println!("Failed to acquire lock");
-                }
-                return Command::perform(services::image::apply(state.image.clone(), state.modifiers.clone()), |r| Message::ImageModified(r));
+                state.apply(Action::ModifierAdded(ModifierAdded::new(modifier)));
+                return state.render();
             }
             Message::ModifierRemoved(idx) => {
                 state.loading = true;
-                RECORD.lock().unwrap().apply(state, Action::ModifierRemoved(ModifierRemoved::new(idx)));
-                return Command::perform(services::image::apply(state.image.clone(), state.modifiers.clone()), |r| Message::ImageModified(r));
+                state.apply(Action::ModifierRemoved(ModifierRemoved::new(idx)));
+                return state.render();
+            }
+            Message::ModifierToggled(idx) => {
+                state.loading = true;
+                state.apply(Action::ModifierToggled(ModifierToggled::new(idx)));
+                return state.render();
+            }
+            Message::ModifierMoved(from, to) => {
+                state.loading = true;
+                state.apply(Action::ModifierMoved(ModifierMoved::new(from, to)));
+                return state.render();
             }
-            Message::ImageModified(image) => {
-                state.handle = ImageHandle::from_pixels(image.width(), image.height(), image.to_vec());
+            Message::ImageModified(generation, image) => {
+                let Some(image) = image else { return Command::none() };
+                if generation != state.generation.load(Ordering::SeqCst) {
+                    return Command::none();
+                }
+                state.handle = display_handle(&image);
                 state.loading = false;
-                return Command::perform(services::image::histogram(image), |r| Message::HistogramRecalculated(r));
+                // The layers under the selected one may have changed with the stack.
+                return Command::batch([
+                    Command::perform(services::image::histogram(image), Message::HistogramRecalculated),
+                    state.measure_curves_input()
+                ]);
+            }
+            Message::PreviewRendered(generation, image) => {
+                let Some(image) = image else { return Command::none() };
+                if generation != state.generation.load(Ordering::SeqCst) {
+                    return Command::none();
+                }
+                state.handle = display_handle(&image);
+                return state.render();
             }
             Message::ModifierOptionsChanged(modifier) => {
-                state.selected_modifier = Some((state.selected_modifier.clone().unwrap().0, modifier))
+                state.selected_modifier = Some((state.selected_modifier.clone().unwrap().0, modifier));
+                state.editor.kernel_draft = None;
+                return state.render_preview();
+            }
+            Message::KernelValueEdited(field, text) => {
+                let Some((idx, Modifier::CustomKernel(opts))) = &state.selected_modifier else { return Command::none() };
+                let mut opts = opts.clone();
+                if let Some(value) = text.trim().parse::<f32>().ok().filter(|v| v.is_finite()) {
+                    field.set(&mut opts, value);
+                }
+                state.selected_modifier = Some((*idx, Modifier::CustomKernel(opts)));
+                state.editor.kernel_draft = Some((field, text));
+                return state.render_preview();
+            }
+            Message::ChannelSelected(channel) => {
+                state.editor.channel = channel;
+            }
+            Message::LevelsAuto => {
+                let Some((idx, Modifier::Levels(_))) = &state.selected_modifier else { return Command::none() };
+                let (idx, document) = (*idx, state.document);
+                // Measured on what goes into the layer, so that pressing it again changes nothing.
+                return Command::perform(
+                    services::image::output_histogram(state.proxy_cache.clone(), state.proxy_layers(&state.modifiers[..idx])),
+                    move |histogram| Message::LevelsAutoMeasured(document, idx, histogram)
+                );
+            }
+            Message::LevelsAutoMeasured(document, idx, histogram) => {
+                let Some((selected, Modifier::Levels(_))) = &state.selected_modifier else { return Command::none() };
+                if document != state.document || *selected != idx {
+                    return Command::none();
+                }
+                state.selected_modifier = Some((idx, Modifier::Levels(LevelsOptions::auto(&histogram, state.editor.levels_clip))));
+                return state.render_preview();
+            }
+            Message::CurvesInputMeasured(document, idx, histogram) => {
+                let Some((selected, Modifier::Curves(_))) = &state.selected_modifier else { return Command::none() };
+                if document != state.document || *selected != idx {
+                    return Command::none();
+                }
+                state.editor.curves_input = histogram;
+            }
+            Message::LevelsClipChanged(clip) => {
+                state.editor.levels_clip = clip;
+            }
+            Message::HueRangeSelected(range) => {
+                state.editor.hue_range = range;
             }
             Message::ModifierOptionsApplied => {
                 state.loading = true;
-                RECORD.lock().unwrap().apply(state, Action::ModifierOptionsApplied(ModifierOptionsApplied::new()));
-                return Command::perform(services::image::apply(state.image.clone(), state.modifiers.clone()), |r| Message::ImageModified(r));
+                state.apply(Action::ModifierOptionsApplied(ModifierOptionsApplied::new()));
+                return state.render();
             }
             Message::ModifierSelected(idx, modifier) => {
-                RECORD.lock().unwrap().apply(state, Action::ModifierSelected(ModifierSelected::new(idx, modifier)))
+                state.editor.kernel_draft = None;
+                state.editor.curves_input = Histogram::default();
+                let previewed = state.previewed_modifiers();
+                state.apply(Action::ModifierSelected(ModifierSelected::new(idx, modifier)));
+                if state.previewed_modifiers() != previewed {
+                    return state.render();
+                }
+                return state.measure_curves_input();
             }
             Message::Undo => {
-                RECORD.lock().unwrap().undo(state);
-                return Command::perform(services::image::apply(state.image.clone(), state.modifiers.clone()), |r| Message::ImageModified(r));
+                state.undo();
+                return state.render();
             }
             Message::Redo => {
-                RECORD.lock().unwrap().redo(state);
-                return Command::perform(services::image::apply(state.image.clone(), state.modifiers.clone()), |r| Message::ImageModified(r));
+                state.redo();
+                return state.render();
+            }
+            Message::HistoryStateSelected(id) => {
+                state.loading = true;
+                state.go_to(id);
+                return state.render();
+            }
+            Message::HistoryStateCompared(id) => {
+                let Some(entry) = state.history.entries().into_iter().find(|e| e.id == id) else { return Command::none() };
+                let name = entry.label.to_string();
+                let modifiers = state.history_stack(id);
+                return state.compare(ComparisonSource::History(id), name, modifiers);
             }
             Message::OpenPicker => {
                 state.loading = true;
-
-                return Command::perform(async {
-                    let file = AsyncFileDialog::new()
-                        .add_filter("image", &["png", "jpg"])
-                        .pick_file()
-                        .await;
-                    file.unwrap().read().await
-                }, |data| {
-                    let img = ImageReader::new(Cursor::new(data))
-                        .with_guessed_format()
-                        .unwrap()
-                        .decode()
-                        .unwrap()
-                        .into_rgba8();
-
-                    Message::Open(img)
-                }
-                )
+                return Command::perform(services::file::open_image(), |result| Message::from_file(result, |(img, metadata)| Message::Open(img, metadata)));
+            }
+            Message::ToggleExport => {
+                state.export_visible = !state.export_visible;
+            }
+            Message::ExportSettingsChanged(settings) => {
+                state.export_settings = settings;
             }
             Message::Save => {
-                let image = state.image.clone();
-                let modifiers = state.modifiers.clone();
-                return Command::perform(async {
-                    let handle = AsyncFileDialog::new()
-                        .set_file_name("edited.png")
-                        .save_file()
-                        .await;
-                    if let Some(handle) = handle {
-                        let img = services::image::apply(image, modifiers).await;
-                        let mut mem = Cursor::new(Vec::<u8>::new());
-
-                        #[cfg(not(target_arch = "wasm32"))]
-                        let format = ImageFormat::from_path(handle.path()).unwrap();
-                        #[cfg(target_arch = "wasm32")]
-                        let format = ImageFormat::Png;
-
-                        img.write_to(&mut mem, format).expect("Error writing to memory buffer");
-                        handle.write(mem.get_ref()).await.expect("Error saving!");
-                    }
-                    ()
-                }, |_| Message::Saved);
+                if let Err(e) = services::file::store_export_settings(&state.export_settings) {
+                    state.notification = Some(e.to_string());
+                }
+                return Command::perform(
+                    services::file::save_image(state.cache.clone(), state.previewed_modifiers(), state.export_settings.clone(), state.metadata.exif.clone()),
+                    |result| Message::from_file(result.map(Some), |_| Message::Saved)
+                );
+            }
+            Message::SavePipeline => {
+                return Command::perform(
+                    services::file::save_pipeline(Pipeline::new(&state.previewed_modifiers())),
+                    |result| Message::from_file(result.map(Some), |_| Message::Saved)
+                );
+            }
+            Message::SaveProject => {
+                let project = Project {
+                    image: state.image.as_ref().clone(),
+                    modifiers: state.previewed_modifiers(),
+                    selected_modifier: state.selected_modifier.as_ref().map(|(i, _)| *i),
+                    histogram_visible: state.histogram_visible,
+                    metadata: state.metadata.clone(),
+                };
+                return Command::perform(
+                    services::file::save_project(project),
+                    |result| Message::from_file(result.map(Some), |_| Message::Saved)
+                );
+            }
+            Message::OpenProjectPicker => {
+                state.loading = true;
+                return EditingView::open_project_picker();
+            }
+            Message::ProjectOpened(project) => {
+                return EditingView::open_project(app, project);
             }
-            Message::Open(data) => {
-                *app = Fairplay::Editing(EditingView::new(data.clone()));
-                return Command::perform(services::image::histogram(data), |r| Message::HistogramRecalculated(r));
+            Message::Open(data, metadata) => {
+                state.close();
+                let mut view = EditingView::new(data);
+                view.metadata = metadata;
+                let command = Command::perform(services::image::histogram(view.image.clone()), Message::HistogramRecalculated);
+                *app = Fairplay::Editing(view);
+                return command;
             }
             Message::Saved => { }
+            Message::Cancelled => {
+                state.loading = false;
+            }
+            Message::Failed(e) => {
+                state.loading = false;
+                state.notification = Some(e.to_string());
+            }
+            Message::NotificationDismissed => {
+                state.notification = None;
+            }
+            Message::SnapshotNameChanged(name) => {
+                state.snapshot_name = name;
+            }
+            Message::SnapshotPinned => {
+                let id = next_id();
+                let number = state.next_snapshot_number;
+                state.next_snapshot_number += 1;
+                let name = match state.snapshot_name.trim() {
+                    "" => { format!("Snapshot {}", number) }
+                    name => { name.to_string() }
+                };
+                state.snapshots.push(Snapshot { id, name, modifiers: state.previewed_modifiers() });
+                state.snapshot_name.clear();
+            }
+            Message::SnapshotRestored(id) => {
+                let Some(snapshot) = state.snapshots.iter().find(|s| s.id == id) else { return Command::none() };
+                state.loading = true;
+                let action = SnapshotRestored::new(snapshot.name.clone(), snapshot.modifiers.clone());
+                state.apply(Action::SnapshotRestored(action));
+                return state.render();
+            }
+            Message::SnapshotRemoved(id) => {
+                state.snapshots.retain(|s| s.id != id);
+                state.compared.retain(|c| c.source != ComparisonSource::Snapshot(id));
+            }
+            Message::SnapshotCompared(id) => {
+                let Some(snapshot) = state.snapshots.iter().find(|s| s.id == id) else { return Command::none() };
+                let (name, modifiers) = (snapshot.name.clone(), snapshot.modifiers.clone());
+                return state.compare(ComparisonSource::Snapshot(id), name, modifiers);
+            }
+            Message::ComparisonRendered(id, image) => {
+                if let Some(comparison) = state.compared.iter_mut().find(|c| c.id == id) {
+                    comparison.handle = Some(display_handle(&image));
+                }
+            }
             Message::HistogramRecalculated(data) => {
                 state.histogram_data = data;
             }
             Message::ToggleHistograms => {
                 state.histogram_visible = !state.histogram_visible;
             }
+            Message::ToggleHistory => {
+                state.history_visible = !state.history_visible;
+            }
+            Message::ToggleInfo => {
+                state.info_visible = !state.info_visible;
+            }
             _ => { panic!("Invalid message") }
         };
 
         Command::none()
     }
 
-    fn view(&self) -> Element<Message> {
+    fn view(&self) -> Element<'_, Message> {
         let dropdown =
         container(
             pick_list(
@@ -165,13 +507,17 @@ impl View for EditingView {
                     Modifier::Grayscale(GrayscaleOptions::default()),
                     Modifier::Channels(ChannelOptions::default()),
                     Modifier::LightnessCorrection(LightnessCorrectionOptions::default()),
+                    Modifier::Curves(CurvesOptions::default()),
+                    Modifier::Levels(LevelsOptions::default()),
+                    Modifier::HueSaturation(HueSaturationOptions::default()),
                     Modifier::BoxBlur(BoxBlurOptions::default()),
                     Modifier::GaussianBlur(GaussianBlurOptions::default()),
                     Modifier::MedianBlur(MedianBlurOptions::default()),
                     Modifier::Sobel(SobelOptions::default()),
-                    Modifier::Laplace,
-                    Modifier::Sharpening,
+                    Modifier::Laplace(LaplaceOptions::default()),
+                    Modifier::Sharpening(SharpeningOptions::default()),
                     Modifier::UnsharpMasking(UnsharpMaskingOptions::default()),
+                    Modifier::CustomKernel(CustomKernelOptions::default()),
                 ],
                 None::<Modifier>,
                 |modifier: Modifier| {
@@ -191,13 +537,29 @@ impl View for EditingView {
             Some(*i)
         } else { None };
 
-        for (i, modifier) in self.modifiers.iter().enumerate() {
+        for (i, layer) in self.modifiers.iter().enumerate() {
+            let mut name = Text::new(format!("{}", layer.modifier))
+                .width(Length::Fill);
+            if !layer.enabled {
+                name = name.style(Color::new(1.0, 1.0, 1.0, 0.4));
+            }
+
             let mut mod_btn = Button::new(
                 Row::new()
+                    .push(name)
                     .push(
-                        Text::new(format!("{}", modifier))
-                            .width(Length::Fill)
-                    ).push(
+                    Button::new(Text::new(String::from(icon_to_char(if layer.enabled { BootstrapIcon::Eye } else { BootstrapIcon::EyeSlash }))).font(BOOTSTRAP_FONT))
+                        .on_press(Message::ModifierToggled(i))
+                        .style(iced::theme::Button::Custom(Box::new(TransparentButtonStyle)))
+                ).push(
+                    Button::new(Text::new(String::from(icon_to_char(BootstrapIcon::ChevronUp))).font(BOOTSTRAP_FONT))
+                        .on_press_maybe((i > 0).then(|| Message::ModifierMoved(i, i - 1)))
+                        .style(iced::theme::Button::Custom(Box::new(TransparentButtonStyle)))
+                ).push(
+                    Button::new(Text::new(String::from(icon_to_char(BootstrapIcon::ChevronDown))).font(BOOTSTRAP_FONT))
+                        .on_press_maybe((i + 1 < self.modifiers.len()).then(|| Message::ModifierMoved(i, i + 1)))
+                        .style(iced::theme::Button::Custom(Box::new(TransparentButtonStyle)))
+                ).push(
                     Button::new(Text::new(String::from(icon_to_char(BootstrapIcon::X))).font(BOOTSTRAP_FONT))
                         .on_press(Message::ModifierRemoved(i))
                         .style(iced::theme::Button::Custom(Box::new(TransparentButtonStyle)))
@@ -206,7 +568,7 @@ impl View for EditingView {
                     .padding([0, 10])
             )
                 .width(Length::Fill)
-                .on_press(Message::ModifierSelected(i, modifier.clone()));
+                .on_press(Message::ModifierSelected(i, layer.modifier.clone()));
 
             if selected_mod_idx.is_some_and(|idx| idx == i) {
                 mod_btn = mod_btn.style(iced::theme::Button::Custom(Box::new(SelectedButtonStyle)))
@@ -217,16 +579,97 @@ impl View for EditingView {
             modifiers = modifiers.push(mod_btn);
         }
 
-        let options = if let Some(modifier) = &self.selected_modifier {
-            Some(modifier_options(&modifier.1))
+        let options = self.selected_modifier.as_ref().map(|modifier| modifier_options(&modifier.1, &self.editor));
+
+        let mut snapshots = Column::new()
+            .push(
+                Row::new()
+                    .push(
+                        text_input("Snapshot name", &self.snapshot_name)
+                            .on_input(Message::SnapshotNameChanged)
+                            .on_submit(Message::SnapshotPinned)
+                    )
+                    .push(
+                        Button::new(Text::new(String::from(icon_to_char(BootstrapIcon::Pin))).font(BOOTSTRAP_FONT))
+                            .on_press(Message::SnapshotPinned)
+                    )
+                    .align_items(Alignment::Center)
+                    .spacing(10)
+                    .padding([0, 10])
+            );
+
+        for snapshot in &self.snapshots {
+            let compared = self.compared.iter().any(|c| c.source == ComparisonSource::Snapshot(snapshot.id));
+            let mut compare_btn = Button::new(Text::new(String::from(icon_to_char(BootstrapIcon::LayoutSplit))).font(BOOTSTRAP_FONT))
+                .on_press(Message::SnapshotCompared(snapshot.id));
+            if compared {
+                compare_btn = compare_btn.style(iced::theme::Button::Custom(Box::new(SelectedButtonStyle)))
+            } else {
+                compare_btn = compare_btn.style(iced::theme::Button::Custom(Box::new(TransparentButtonStyle)))
+            }
+
+            snapshots = snapshots.push(
+                Button::new(
+                    Row::new()
+                        .push(
+                            Text::new(snapshot.name.as_str())
+                                .width(Length::Fill)
+                        ).push(compare_btn)
+                        .push(
+                            Button::new(Text::new(String::from(icon_to_char(BootstrapIcon::X))).font(BOOTSTRAP_FONT))
+                                .on_press(Message::SnapshotRemoved(snapshot.id))
+                                .style(iced::theme::Button::Custom(Box::new(TransparentButtonStyle)))
+                        )
+                        .align_items(Alignment::Center)
+                        .padding([0, 10])
+                )
+                    .width(Length::Fill)
+                    .on_press(Message::SnapshotRestored(snapshot.id))
+                    .style(iced::theme::Button::Custom(Box::new(TransparentButtonStyle)))
+            );
+        }
+
+        let image: Element<'_, Message> = if self.compared.is_empty() {
+            iced::widget::image::viewer(self.handle.clone())
+                .min_scale(0.5)
+                .width(Length::FillPortion(4))
+                .height(Length::Fill)
+                .into()
         } else {
-            None
-        };
+            // With a single snapshot or state picked, it is compared against the current stack.
+            let mut panes = vec![];
+            if self.compared.len() == 1 {
+                panes.push(("Current", Some(&self.handle)));
+            }
+            for comparison in &self.compared {
+                panes.push((comparison.name.as_str(), comparison.handle.as_ref()));
+            }
 
-        let image = iced::widget::image::viewer(self.handle.clone())
-            .min_scale(0.5)
-            .width(Length::FillPortion(4))
-            .height(Length::Fill);
+            let mut comparison = Row::new()
+                .spacing(10)
+                .width(Length::FillPortion(4))
+                .height(Length::Fill);
+            for (name, handle) in panes {
+                let content: Element<'_, Message> = match handle {
+                    Some(handle) => {
+                        iced::widget::image::viewer(handle.clone())
+                            .min_scale(0.5)
+                            .width(Length::Fill)
+                            .height(Length::Fill)
+                            .into()
+                    }
+                    None => { Text::new("Rendering...").height(Length::Fill).into() }
+                };
+                comparison = comparison.push(
+                    Column::new()
+                        .push(Text::new(name))
+                        .push(content)
+                        .align_items(Alignment::Center)
+                        .width(Length::Fill)
+                );
+            }
+            comparison.into()
+        };
 
         let menu = Container::new(
             Row::new()
@@ -234,18 +677,27 @@ impl View for EditingView {
                     button("Open").on_press(Message::OpenPicker)
                 )
                 .push(
-                    button("Save").on_press(Message::Save)
+                    button("Export").on_press(Message::ToggleExport)
+                )
+                .push(
+                    button("Open project").on_press(Message::OpenProjectPicker)
+                )
+                .push(
+                    button("Save project").on_press(Message::SaveProject)
+                )
+                .push(
+                    button("Save pipeline").on_press(Message::SavePipeline)
                 )
                 .push(
                     button("Undo").on_press_maybe(
-                        if RECORD.lock().unwrap().can_undo() {
+                        if self.history.can_undo() {
                             Some(Message::Undo)
                         } else { None }
                     )
                 )
                 .push(
                     button("Redo").on_press_maybe(
-                        if RECORD.lock().unwrap().can_redo() {
+                        if self.history.can_redo() {
                             Some(Message::Redo)
                         } else { None }
                     )
@@ -253,6 +705,12 @@ impl View for EditingView {
                 .push(
                     button("Toggle histograms").on_press(Message::ToggleHistograms)
                 )
+                .push(
+                    button("History").on_press(Message::ToggleHistory)
+                )
+                .push(
+                    button("Info").on_press(Message::ToggleInfo)
+                )
                 .width(Length::Fill)
                 .align_items(Alignment::Start)
                 .spacing(10)
@@ -283,10 +741,95 @@ impl View for EditingView {
             Some(histograms)
         } else { None };
 
+        let history = if self.history_visible {
+            let mut states = Column::new();
+            for entry in self.history.entries() {
+                let mut label = Text::new(entry.label);
+                if !entry.active {
+                    label = label.style(Color::new(1.0, 1.0, 1.0, 0.4));
+                }
+
+                let mut compare_btn = Button::new(Text::new(String::from(icon_to_char(BootstrapIcon::LayoutSplit))).font(BOOTSTRAP_FONT))
+                    .on_press(Message::HistoryStateCompared(entry.id));
+                if self.compared.iter().any(|c| c.source == ComparisonSource::History(entry.id)) {
+                    compare_btn = compare_btn.style(iced::theme::Button::Custom(Box::new(SelectedButtonStyle)))
+                } else {
+                    compare_btn = compare_btn.style(iced::theme::Button::Custom(Box::new(TransparentButtonStyle)))
+                }
+
+                let mut state_btn = Button::new(
+                    Row::new()
+                        .push(Space::with_width(Length::Fixed(20.0 * entry.level as f32)))
+                        .push(label.width(Length::Fill))
+                        .push(compare_btn)
+                        .align_items(Alignment::Center)
+                )
+                    .width(Length::Fill)
+                    .on_press(Message::HistoryStateSelected(entry.id));
+
+                if entry.current {
+                    state_btn = state_btn.style(iced::theme::Button::Custom(Box::new(SelectedButtonStyle)))
+                } else {
+                    state_btn = state_btn.style(iced::theme::Button::Custom(Box::new(TransparentButtonStyle)))
+                }
+
+                states = states.push(state_btn);
+            }
+
+            let history = Container::new(
+                Scrollable::new(states)
+                    .width(Length::FillPortion(1))
+                    .height(Length::Fill)
+            ).style(container::Appearance::default()
+                .with_background(Background::from(Color::new(0.0, 0.0, 0.0, 0.3)))
+            );
+            Some(history)
+        } else { None };
+
+        let info = if self.info_visible {
+            let mut fields = Column::new()
+                .push(Text::new(format!("{} × {}", self.image.width(), self.image.height())))
+                .spacing(5)
+                .padding(10);
+            if self.metadata.summary.is_empty() {
+                fields = fields.push(Text::new("No EXIF metadata"));
+            }
+            for (name, value) in &self.metadata.summary {
+                fields = fields.push(
+                    Row::new()
+                        .push(Text::new(name.as_str()).width(Length::FillPortion(1)))
+                        .push(Text::new(value.as_str()).width(Length::FillPortion(2)))
+                        .spacing(10)
+                );
+            }
+
+            let info = Container::new(
+                Scrollable::new(fields)
+                    .width(Length::FillPortion(1))
+                    .height(Length::Fill)
+            ).style(container::Appearance::default()
+                .with_background(Background::from(Color::new(0.0, 0.0, 0.0, 0.3)))
+            );
+            Some(info)
+        } else { None };
+
+        let export = if self.export_visible {
+            let export = Container::new(
+                Column::new()
+                    .push(export_options(&self.export_settings))
+                    .width(Length::FillPortion(1))
+                    .height(Length::Fill)
+            ).style(container::Appearance::default()
+                .with_background(Background::from(Color::new(0.0, 0.0, 0.0, 0.3)))
+            );
+            Some(export)
+        } else { None };
+
         let panel = Container::new(
             Column::new()
                 .push(dropdown)
                 .push(modifiers)
+                .push(snapshots)
                 .push(Space::new(Length::Fill, Length::Fill))
                 .push_maybe(options)
                 .spacing(10)
@@ -300,11 +843,15 @@ impl View for EditingView {
         let row = Row::new()
             .push(image)
             .push(panel)
+            .push_maybe(history)
+            .push_maybe(export)
+            .push_maybe(info)
             .push_maybe(histograms)
             .height(Length::Fill);
 
         let column = Column::new()
             .push(menu)
+            .push_maybe(self.notification.clone().map(notification))
             .push(row)
             .into();
 
@@ -314,4 +861,95 @@ impl View for EditingView {
             column
         }
     }
-}
\ No newline at end of file
+}
+
+/// Rounds a rendered image to 8 bits for the image viewer.
+fn display_handle(image: &Rgba32FImage) -> ImageHandle {
+    let rgba = fairplay_core::to_rgba8(image);
+    ImageHandle::from_pixels(rgba.width(), rgba.height(), rgba.into_raw())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editing(app: &Fairplay) -> &EditingView {
+        let Fairplay::Editing(view) = app else { panic!("Not editing") };
+        view
+    }
+
+    #[test]
+    fn results_for_a_replaced_document_are_dropped() {
+        let mut app = Fairplay::Editing(EditingView::new(Rgba32FImage::new(4, 4)));
+        let previous = editing(&app);
+        let _ = previous.render();
+        let (stale, previous_generation) = (previous.generation.load(Ordering::SeqCst), previous.generation.clone());
+        let previous_document = previous.document;
+
+        let _ = EditingView::update(&mut app, Message::Open(Rgba32FImage::new(2, 2), Metadata::default()));
+        // The running render of the replaced document is cancelled.
+        assert_ne!(previous_generation.load(Ordering::SeqCst), stale);
+
+        let view = editing(&app);
+        let _ = view.render();
+        let handle = view.handle.clone();
+        let stale_image = Some(Arc::new(Rgba32FImage::from_pixel(4, 4, image::Rgba([1.0; 4]))));
+        let _ = EditingView::update(&mut app, Message::ImageModified(stale, stale_image.clone()));
+        let _ = EditingView::update(&mut app, Message::PreviewRendered(stale, stale_image));
+        assert_eq!(editing(&app).handle, handle);
+
+        let Fairplay::Editing(view) = &mut app else { unreachable!() };
+        view.modifiers = vec![Layer::new(Modifier::Levels(LevelsOptions::default()))];
+        view.selected_modifier = Some((0, Modifier::Levels(LevelsOptions::default())));
+        let histogram = Histogram { lightness: vec![0; 256], red: vec![1; 256], green: vec![0; 256], blue: vec![0; 256] };
+        let _ = EditingView::update(&mut app, Message::LevelsAutoMeasured(previous_document, 0, histogram));
+        assert_eq!(editing(&app).selected_modifier, Some((0, Modifier::Levels(LevelsOptions::default()))));
+    }
+
+    #[test]
+    fn snapshots_take_the_previewed_options() {
+        let mut app = Fairplay::Editing(EditingView::new(Rgba32FImage::new(2, 2)));
+        let pending = Modifier::BoxBlur(BoxBlurOptions { size: 7, ..BoxBlurOptions::default() });
+        let Fairplay::Editing(view) = &mut app else { unreachable!() };
+        view.modifiers = vec![Layer::new(Modifier::BoxBlur(BoxBlurOptions::default()))];
+        view.selected_modifier = Some((0, pending.clone()));
+
+        let _ = EditingView::update(&mut app, Message::SnapshotPinned);
+        let view = editing(&app);
+        assert_eq!(view.snapshots[0].modifiers, vec![Layer::new(pending)]);
+        assert_eq!(view.snapshots[0].name, "Snapshot 1");
+    }
+
+    #[test]
+    fn curves_show_the_input_of_their_layer() {
+        let mut app = Fairplay::Editing(EditingView::new(Rgba32FImage::new(2, 2)));
+        let _ = EditingView::update(&mut app, Message::ModifierAdded(Modifier::BoxBlur(BoxBlurOptions::default())));
+        let _ = EditingView::update(&mut app, Message::ModifierAdded(Modifier::Curves(CurvesOptions::default())));
+        let document = editing(&app).document;
+        let histogram = Histogram { lightness: vec![1; 256], red: vec![0; 256], green: vec![0; 256], blue: vec![0; 256] };
+
+        let _ = EditingView::update(&mut app, Message::CurvesInputMeasured(document, 0, histogram.clone()));
+        assert!(editing(&app).editor.curves_input.lightness.is_empty());
+        let _ = EditingView::update(&mut app, Message::CurvesInputMeasured(document, 1, histogram.clone()));
+        assert_eq!(editing(&app).editor.curves_input.lightness, histogram.lightness);
+    }
+
+    #[test]
+    fn history_states_are_compared_without_leaving_the_current_one() {
+        let mut app = Fairplay::Editing(EditingView::new(Rgba32FImage::new(2, 2)));
+        let blur = Modifier::BoxBlur(BoxBlurOptions::default());
+        let _ = EditingView::update(&mut app, Message::ModifierAdded(blur.clone()));
+        let _ = EditingView::update(&mut app, Message::ModifierAdded(blur.clone()));
+        let first = editing(&app).history.entries()[1].id;
+
+        let _ = EditingView::update(&mut app, Message::HistoryStateCompared(first));
+        let view = editing(&app);
+        assert_eq!(view.modifiers.len(), 2);
+        assert!(view.history.entries().last().unwrap().current);
+        assert_eq!(view.compared.len(), 1);
+        assert_eq!(view.compared[0].source, ComparisonSource::History(first));
+
+        let _ = EditingView::update(&mut app, Message::HistoryStateCompared(first));
+        assert!(editing(&app).compared.is_empty());
+    }
+}
@@ -1,11 +1,73 @@
-use iced::Element;
-use iced::widget::{Button, checkbox, Column, Row, Text};
+use fairplay_core::models::border::{Border, BorderMode};
+use fairplay_core::models::curve::{CurveChannel, CurvePoint};
+use fairplay_core::models::histogram::Histogram;
+use fairplay_core::models::hue::{ColorModel, HueAdjustment, HueRange};
+use fairplay_core::models::kernel::KernelPreset;
+use fairplay_core::models::modifier::{BoxBlurOptions, ChannelLevels, ChannelOptions, CurvesOptions, CustomKernelOptions, GaussianBlurOptions, GrayscaleOptions, HueSaturationOptions, LaplaceOptions, LevelsOptions, LightnessCorrectionOptions, MedianBlurOptions, Modifier, NegativeOptions, SharpeningOptions, SobelOptions, ThresholdingOptions, UnsharpMaskingOptions};
+use iced::{Alignment, Color, Element, Length};
+use iced::widget::{Button, checkbox, Column, pick_list, Row, slider, Text, text_input};
 
 use crate::fairplay::Message;
 use crate::interface::components::{named_slider, ranged_named_slider};
-use crate::models::modifier::{BoxBlurOptions, ChannelOptions, GaussianBlurOptions, GrayscaleOptions, LightnessCorrectionOptions, MedianBlurOptions, Modifier, NegativeOptions, SobelOptions, ThresholdingOptions, UnsharpMaskingOptions};
+use crate::interface::curves::curve_editor;
 
-pub fn modifier_options<'a>(modifier: &'a Modifier) -> Element<'a, Message> {
+/// A number of the custom kernel editor that is typed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KernelField {
+    /// Row and column of a weight.
+    Weight(usize, usize),
+    Divisor
+}
+
+impl KernelField {
+    fn value(self, opts: &CustomKernelOptions) -> f32 {
+        match self {
+            KernelField::Weight(y, x) => { opts.kernel.get(y).and_then(|row| row.get(x)).copied().unwrap_or(0.0) }
+            KernelField::Divisor => { opts.divisor }
+        }
+    }
+
+    pub fn set(self, opts: &mut CustomKernelOptions, value: f32) {
+        match self {
+            KernelField::Weight(y, x) => {
+                if let Some(weight) = opts.kernel.get_mut(y).and_then(|row| row.get_mut(x)) {
+                    *weight = value;
+                }
+            }
+            KernelField::Divisor => { opts.divisor = value }
+        }
+    }
+}
+
+/// State of the option editors that isn't part of the modifier.
+#[derive(Debug)]
+pub struct EditorState {
+    /// Text of the custom kernel field being typed in, which may not be a number yet.
+    pub kernel_draft: Option<(KernelField, String)>,
+    /// Channel shown by the curves and levels editors.
+    pub channel: CurveChannel,
+    /// Percentage of pixels auto levels clips on either side.
+    pub levels_clip: f32,
+    /// Colour range shown by the hue / saturation editor.
+    pub hue_range: HueRange,
+    /// Histogram of what goes into the selected curves modifier, drawn behind the curve.
+    pub curves_input: Histogram
+}
+
+impl Default for EditorState {
+    fn default() -> Self {
+        EditorState {
+            kernel_draft: None,
+            channel: CurveChannel::default(),
+            levels_clip: 0.1,
+            hue_range: HueRange::default(),
+            curves_input: Histogram::default(),
+        }
+    }
+}
+
+/// `histogram` is drawn behind curves.
+pub fn modifier_options<'a>(modifier: &'a Modifier, editor: &'a EditorState) -> Element<'a, Message> {
     let opts = match modifier {
         Modifier::Negative(opts) => { negative_modopts(opts) }
         Modifier::Thresholding(opts) => { thresholding_modopts(opts) }
@@ -16,9 +78,13 @@ pub fn modifier_options<'a>(modifier: &'a Modifier) -> Element<'a, Message> {
         Modifier::GaussianBlur(opts) => { gaussian_blur_modopts(opts) }
         Modifier::MedianBlur(opts) => { median_blur_modopts(opts) }
         Modifier::Sobel(opts) => { sobel_modopts(opts) }
-        Modifier::Sharpening => { return Column::new().into() }
+        Modifier::Sharpening(opts) => { sharpening_modopts(opts) }
         Modifier::UnsharpMasking(opts) => { unsharp_masking_modopts(opts) }
-        Modifier::Laplace => { return Column::new().into() }
+        Modifier::Laplace(opts) => { laplace_modopts(opts) }
+        Modifier::CustomKernel(opts) => { custom_kernel_modopts(opts, &editor.kernel_draft) }
+        Modifier::Curves(opts) => { curves_modopts(opts, editor.channel, &editor.curves_input) }
+        Modifier::Levels(opts) => { levels_modopts(opts, editor) }
+        Modifier::HueSaturation(opts) => { hue_saturation_modopts(opts, editor.hue_range) }
     };
 
     let apply = Button::new("Apply")
@@ -74,28 +140,223 @@ fn channels_modopts<'a>(opts: &'a ChannelOptions) -> Element<'a, Message> {
 }
 
 fn lightness_correction_modopts<'a>(opts: &LightnessCorrectionOptions) -> Element<'a, Message> {
-    named_slider("Exponent", opts.exponent, |x| Message::ModifierOptionsChanged(Modifier::LightnessCorrection(LightnessCorrectionOptions { exponent: x }))).into()
+    named_slider("Exponent", opts.exponent, |x| Message::ModifierOptionsChanged(Modifier::LightnessCorrection(LightnessCorrectionOptions { exponent: x })))
 }
 
-fn box_blur_modopts<'a>(opts: &BoxBlurOptions) -> Element<'a, Message> {
-    ranged_named_slider("Box size", 3..=25, 2, opts.size, |x| Message::ModifierOptionsChanged(Modifier::BoxBlur(BoxBlurOptions { size: x })))
+fn curves_modopts<'a>(opts: &'a CurvesOptions, channel: CurveChannel, histogram: &Histogram) -> Element<'a, Message> {
+    let changed = move |points: Vec<CurvePoint>| {
+        let mut opts = opts.clone();
+        *opts.curve_mut(channel) = points;
+        Message::ModifierOptionsChanged(Modifier::Curves(opts))
+    };
+    let (data, color) = match channel {
+        CurveChannel::Rgb | CurveChannel::Luminance => { (histogram.lightness.clone(), Color::new(0.5, 0.5, 0.5, 1.0)) }
+        CurveChannel::Red => { (histogram.red.clone(), Color::new(1.0, 0.0, 0.0, 1.0)) }
+        CurveChannel::Green => { (histogram.green.clone(), Color::new(0.0, 1.0, 0.0, 1.0)) }
+        CurveChannel::Blue => { (histogram.blue.clone(), Color::new(0.0, 0.0, 1.0, 1.0)) }
+    };
+
+    Column::new()
+        .push(Row::new()
+            .push(Text::new("Channel"))
+            .push(pick_list(CurveChannel::ALL, Some(channel), Message::ChannelSelected))
+            .push(Button::new("Reset").on_press(changed(CurvesOptions::default().curve(channel).clone())))
+            .align_items(Alignment::Center)
+            .spacing(10)
+        )
+        .push(curve_editor(opts.curve(channel), data, color, changed).width(Length::Fill).height(Length::Fixed(250.0)))
+        .push(Text::new("Click to add a point, drag to move it and right click to remove it.").size(12))
+        .spacing(10)
+        .into()
 }
 
-fn gaussian_blur_modopts<'a>(opts: &GaussianBlurOptions) -> Element<'a, Message> {
-    ranged_named_slider("Box size", 3..=25, 2, opts.size, |x| Message::ModifierOptionsChanged(Modifier::GaussianBlur(GaussianBlurOptions { size: x })))
+fn levels_modopts<'a>(opts: &'a LevelsOptions, editor: &EditorState) -> Element<'a, Message> {
+    let channel = editor.channel;
+    let levels = *opts.levels(channel);
+    let changed = move |levels: ChannelLevels| {
+        let mut opts = opts.clone();
+        *opts.levels_mut(channel) = levels;
+        Message::ModifierOptionsChanged(Modifier::Levels(opts))
+    };
+
+    Column::new()
+        .push(Row::new()
+            .push(Text::new("Channel"))
+            .push(pick_list(CurveChannel::ALL, Some(channel), Message::ChannelSelected))
+            .push(Button::new("Reset").on_press(changed(ChannelLevels::default())))
+            .align_items(Alignment::Center)
+            .spacing(10)
+        )
+        .push(named_slider("Input black", levels.input_black, move |x| changed(ChannelLevels { input_black: x, ..levels })))
+        .push(named_slider("Input white", levels.input_white, move |x| changed(ChannelLevels { input_white: x, ..levels })))
+        .push(Row::new()
+            .push(Text::new("Gamma"))
+            .push(slider(0.1..=5.0, levels.gamma, move |x| changed(ChannelLevels { gamma: x, ..levels })).step(0.01))
+            .push(Text::new(format!("{:.2}", levels.gamma)))
+            .spacing(10)
+        )
+        .push(named_slider("Output black", levels.output_black, move |x| changed(ChannelLevels { output_black: x, ..levels })))
+        .push(named_slider("Output white", levels.output_white, move |x| changed(ChannelLevels { output_white: x, ..levels })))
+        .push(Row::new()
+            .push(Button::new("Auto").on_press(Message::LevelsAuto))
+            .push(Text::new("Clip"))
+            .push(slider(0.0..=5.0, editor.levels_clip, Message::LevelsClipChanged).step(0.05))
+            .push(Text::new(format!("{:.2}%", editor.levels_clip)))
+            .align_items(Alignment::Center)
+            .spacing(10)
+        )
+        .spacing(10)
+        .into()
 }
 
-fn median_blur_modopts<'a>(opts: &MedianBlurOptions) -> Element<'a, Message> {
-    ranged_named_slider("Box size", 3..=25, 2, opts.size, |x| Message::ModifierOptionsChanged(Modifier::MedianBlur(MedianBlurOptions { size: x })))
+fn hue_saturation_modopts<'a>(opts: &'a HueSaturationOptions, range: HueRange) -> Element<'a, Message> {
+    // Colorize only uses the master adjustment.
+    let range = if opts.colorize { HueRange::Master } else { range };
+    let adjustment = *opts.adjustment(range);
+    let changed = move |adjustment: HueAdjustment| {
+        let mut opts = opts.clone();
+        *opts.adjustment_mut(range) = adjustment;
+        Message::ModifierOptionsChanged(Modifier::HueSaturation(opts))
+    };
+    let mut ranges = Row::new()
+        .push(Text::new("Colours"))
+        .align_items(Alignment::Center)
+        .spacing(10);
+    if !opts.colorize {
+        ranges = ranges.push(pick_list(HueRange::ALL, Some(range), Message::HueRangeSelected));
+    }
+
+    Column::new()
+        .push(Row::new()
+            .push(Text::new("Model"))
+            .push(pick_list(ColorModel::ALL, Some(opts.model), move |model| Message::ModifierOptionsChanged(Modifier::HueSaturation(HueSaturationOptions { model, ..opts.clone() }))))
+            .push(checkbox("Colorize", opts.colorize).on_toggle(move |v| Message::ModifierOptionsChanged(Modifier::HueSaturation(HueSaturationOptions { colorize: v, ..opts.clone() }))))
+            .align_items(Alignment::Center)
+            .spacing(10)
+        )
+        .push(ranges.push(Button::new("Reset").on_press(changed(HueAdjustment::default()))))
+        .push(signed_slider("Hue", 180, adjustment.hue, move |x| changed(HueAdjustment { hue: x, ..adjustment })))
+        .push(signed_slider("Saturation", 100, adjustment.saturation, move |x| changed(HueAdjustment { saturation: x, ..adjustment })))
+        .push(signed_slider(if opts.model == ColorModel::Hsv { "Value" } else { "Lightness" }, 100, adjustment.lightness, move |x| changed(HueAdjustment { lightness: x, ..adjustment })))
+        .spacing(10)
+        .into()
+}
+
+/// Slider from `-limit` to `limit`.
+fn signed_slider<'a>(name: &'a str, limit: i16, value: i16, on_change: impl Fn(i16) -> Message + 'a) -> Element<'a, Message> {
+    Row::new()
+        .push(Text::new(name))
+        .push(slider(-limit..=limit, value, on_change))
+        .push(Text::new(value.to_string()))
+        .spacing(10)
+        .into()
+}
+
+fn box_blur_modopts<'a>(opts: &'a BoxBlurOptions) -> Element<'a, Message> {
+    Column::new()
+        .push(ranged_named_slider("Box size", 3..=25, 2, opts.size, |x| Message::ModifierOptionsChanged(Modifier::BoxBlur(BoxBlurOptions { size: x, ..opts.clone() }))))
+        .push(border_options(opts.border, |border| Message::ModifierOptionsChanged(Modifier::BoxBlur(BoxBlurOptions { border, ..opts.clone() }))))
+        .into()
+}
+
+fn gaussian_blur_modopts<'a>(opts: &'a GaussianBlurOptions) -> Element<'a, Message> {
+    Column::new()
+        .push(ranged_named_slider("Box size", 3..=25, 2, opts.size, |x| Message::ModifierOptionsChanged(Modifier::GaussianBlur(GaussianBlurOptions { size: x, ..opts.clone() }))))
+        .push(border_options(opts.border, |border| Message::ModifierOptionsChanged(Modifier::GaussianBlur(GaussianBlurOptions { border, ..opts.clone() }))))
+        .into()
+}
+
+fn median_blur_modopts<'a>(opts: &'a MedianBlurOptions) -> Element<'a, Message> {
+    Column::new()
+        .push(ranged_named_slider("Box size", 3..=25, 2, opts.size, |x| Message::ModifierOptionsChanged(Modifier::MedianBlur(MedianBlurOptions { size: x, ..opts.clone() }))))
+        .push(border_options(opts.border, |border| Message::ModifierOptionsChanged(Modifier::MedianBlur(MedianBlurOptions { border, ..opts.clone() }))))
+        .into()
 }
 
 fn sobel_modopts<'a>(opts: &'a SobelOptions) -> Element<'a, Message> {
     Column::new()
         .push(checkbox("Horizontal", opts.horizontal).on_toggle(|v| Message::ModifierOptionsChanged(Modifier::Sobel(SobelOptions { horizontal: v, ..opts.clone() }))))
         .push(checkbox("Vertical", opts.vertical).on_toggle(|v| Message::ModifierOptionsChanged(Modifier::Sobel(SobelOptions { vertical: v, ..opts.clone() }))))
+        .push(border_options(opts.border, |border| Message::ModifierOptionsChanged(Modifier::Sobel(SobelOptions { border, ..opts.clone() }))))
         .into()
 }
 
-fn unsharp_masking_modopts<'a>(opts: &UnsharpMaskingOptions) -> Element<'a, Message> {
-    ranged_named_slider("Box size", 3..=25, 2, opts.blur_size, |x| Message::ModifierOptionsChanged(Modifier::UnsharpMasking(UnsharpMaskingOptions { blur_size: x })))
-}
\ No newline at end of file
+fn laplace_modopts<'a>(opts: &LaplaceOptions) -> Element<'a, Message> {
+    border_options(opts.border, |border| Message::ModifierOptionsChanged(Modifier::Laplace(LaplaceOptions { border })))
+}
+
+fn sharpening_modopts<'a>(opts: &SharpeningOptions) -> Element<'a, Message> {
+    border_options(opts.border, |border| Message::ModifierOptionsChanged(Modifier::Sharpening(SharpeningOptions { border })))
+}
+
+fn unsharp_masking_modopts<'a>(opts: &'a UnsharpMaskingOptions) -> Element<'a, Message> {
+    Column::new()
+        .push(ranged_named_slider("Box size", 3..=25, 2, opts.blur_size, |x| Message::ModifierOptionsChanged(Modifier::UnsharpMasking(UnsharpMaskingOptions { blur_size: x, ..opts.clone() }))))
+        .push(border_options(opts.border, |border| Message::ModifierOptionsChanged(Modifier::UnsharpMasking(UnsharpMaskingOptions { border, ..opts.clone() }))))
+        .into()
+}
+
+fn custom_kernel_modopts<'a>(opts: &'a CustomKernelOptions, draft: &'a Option<(KernelField, String)>) -> Element<'a, Message> {
+    let changed = |opts: CustomKernelOptions| Message::ModifierOptionsChanged(Modifier::CustomKernel(opts));
+    let field = |field: KernelField, width: f32| {
+        let text = match draft {
+            Some((edited, text)) if *edited == field => { text.clone() }
+            _ => { field.value(opts).to_string() }
+        };
+        text_input("0", &text)
+            .on_input(move |text| Message::KernelValueEdited(field, text))
+            .width(Length::Fixed(width))
+    };
+
+    let mut weights = Column::new().spacing(5);
+    for (y, row) in opts.kernel.iter().enumerate() {
+        weights = weights.push((0..row.len()).fold(Row::new().spacing(5), |cells, x| cells.push(field(KernelField::Weight(y, x), 50.0))));
+    }
+
+    Column::new()
+        .push(pick_list(KernelPreset::ALL, None::<KernelPreset>, move |preset| changed(CustomKernelOptions { border: opts.border, ..preset.options() }))
+            .placeholder("Load preset"))
+        .push(ranged_named_slider("Kernel size", 1..=9, 2, opts.size().min(u8::MAX as usize) as u8, move |x| changed(opts.resized(x as usize))))
+        .push(weights)
+        .push(Row::new()
+            .push(Text::new("Divisor"))
+            .push(field(KernelField::Divisor, 80.0))
+            .push(checkbox("Normalise", opts.normalize).on_toggle(move |v| changed(CustomKernelOptions { normalize: v, ..opts.clone() })))
+            .align_items(Alignment::Center)
+            .spacing(10)
+        )
+        .push(Row::new()
+            .push(Text::new("Bias"))
+            .push(slider(-255.0..=255.0, opts.bias, move |v| changed(CustomKernelOptions { bias: v, ..opts.clone() })).step(1.0))
+            .push(Text::new(opts.bias.to_string()))
+            .spacing(10)
+        )
+        .push(border_options(opts.border, move |border| changed(CustomKernelOptions { border, ..opts.clone() })))
+        .spacing(10)
+        .into()
+}
+
+/// Edge handling of the neighbourhood modifiers. `on_change` builds the modifier with the new border.
+fn border_options<'a>(border: Border, on_change: impl Fn(Border) -> Message + Clone + 'a) -> Element<'a, Message> {
+    let mode_changed = on_change.clone();
+    let mut options = Column::new()
+        .push(Row::new()
+            .push(Text::new("Edges"))
+            .push(pick_list(BorderMode::ALL, Some(border.mode), move |mode| mode_changed(Border { mode, ..border })))
+            .align_items(Alignment::Center)
+            .spacing(10)
+        );
+
+    if border.mode == BorderMode::Constant {
+        for (i, name) in ["Red", "Green", "Blue"].into_iter().enumerate() {
+            let on_change = on_change.clone();
+            options = options.push(named_slider(name, border.color[i], move |v| {
+                let mut color = border.color;
+                color[i] = v;
+                on_change(Border { color, ..border })
+            }));
+        }
+    }
+
+    options.spacing(10).into()
+}
@@ -0,0 +1,63 @@
+use iced::{Alignment, Element, Length};
+use iced::widget::{Button, checkbox, Column, pick_list, Row, Text};
+
+use crate::fairplay::Message;
+use crate::interface::components::ranged_named_slider;
+use crate::models::export::{BitDepth, ExportFormat, ExportSettings, OutputProfile, PngCompression, PngFilter};
+
+pub fn export_options<'a>(settings: &'a ExportSettings) -> Element<'a, Message> {
+    let changed = |settings: ExportSettings| Message::ExportSettingsChanged(settings);
+
+    let mut options = Column::new()
+        .push(Text::new("Export"))
+        .push(labeled("Format", pick_list(ExportFormat::ALL, Some(settings.format), move |format| changed(ExportSettings { format, ..settings.clone() }))));
+
+    match settings.format {
+        ExportFormat::Jpeg => {
+            options = options.push(ranged_named_slider("Quality", 1..=100, 1, settings.jpeg_quality, move |jpeg_quality| changed(ExportSettings { jpeg_quality, ..settings.clone() })));
+        }
+        ExportFormat::Png => {
+            options = options
+                .push(labeled("Compression", pick_list(PngCompression::ALL, Some(settings.png_compression), move |png_compression| changed(ExportSettings { png_compression, ..settings.clone() }))))
+                .push(labeled("Filter", pick_list(PngFilter::ALL, Some(settings.png_filter), move |png_filter| changed(ExportSettings { png_filter, ..settings.clone() }))));
+        }
+        // Lossy WebP needs libwebp, which isn't available in the browser.
+        #[cfg(not(target_arch = "wasm32"))]
+        ExportFormat::WebP => {
+            options = options.push(checkbox("Lossless", settings.webp_lossless).on_toggle(move |webp_lossless| changed(ExportSettings { webp_lossless, ..settings.clone() })));
+            if !settings.webp_lossless {
+                options = options.push(ranged_named_slider("Quality", 0..=100, 1, settings.webp_quality, move |webp_quality| changed(ExportSettings { webp_quality, ..settings.clone() })));
+            }
+        }
+        _ => { }
+    }
+
+    if settings.format.supports_16_bit() {
+        options = options.push(labeled("Bit depth", pick_list(BitDepth::ALL, Some(settings.bit_depth), move |bit_depth| changed(ExportSettings { bit_depth, ..settings.clone() }))));
+    }
+    if settings.format.supports_alpha() {
+        options = options.push(checkbox("Keep alpha", settings.keep_alpha).on_toggle(move |keep_alpha| changed(ExportSettings { keep_alpha, ..settings.clone() })));
+    }
+
+    if settings.format.supports_color_profile() {
+        options = options.push(labeled("Colour profile", pick_list(OutputProfile::ALL, Some(settings.profile), move |profile| changed(ExportSettings { profile, ..settings.clone() }))));
+    }
+    if settings.format.supports_metadata() {
+        options = options.push(checkbox("Keep metadata", settings.keep_metadata).on_toggle(move |keep_metadata| changed(ExportSettings { keep_metadata, ..settings.clone() })));
+    }
+
+    options
+        .push(Button::new("Export...").on_press(Message::Save))
+        .spacing(10)
+        .padding(10)
+        .into()
+}
+
+fn labeled<'a>(name: &'a str, content: impl Into<Element<'a, Message>>) -> Element<'a, Message> {
+    Row::new()
+        .push(Text::new(name).width(Length::Fill))
+        .push(content)
+        .align_items(Alignment::Center)
+        .spacing(10)
+        .into()
+}
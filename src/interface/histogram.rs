@@ -7,14 +7,6 @@ pub fn histogram<Message>(data: Vec<u32>, color: Color) -> Canvas<HistogramProgr
     canvas(HistogramProgram::new(data, color))
 }
 
-#[derive(Default, Clone, Debug)]
-pub struct Histogram {
-    pub(crate) lightness: Vec<u32>,
-    pub(crate) red: Vec<u32>,
-    pub(crate) green: Vec<u32>,
-    pub(crate) blue: Vec<u32>
-}
-
 pub struct HistogramProgram {
     data: Vec<u32>,
     cache: Cache,
@@ -1,21 +1,18 @@
-use std::io::Cursor;
-
 use iced::{Alignment, alignment, Command, Element, Length};
 use iced::widget::{Button, Column, Container, Row, Text};
 use iced_aw::{BOOTSTRAP_FONT, BootstrapIcon};
 use iced_aw::graphics::icons::icon_to_char;
-use image::io::Reader as ImageReader;
-use rfd::AsyncFileDialog;
 
 use crate::fairplay::{Fairplay, Message};
-use crate::interface::components::with_spinner;
+use crate::interface::components::{notification, with_spinner};
 use crate::interface::editing::EditingView;
 use crate::interface::View;
 use crate::services;
 
 #[derive(Default)]
 pub struct HomeView {
-    loading: bool
+    loading: bool,
+    notification: Option<String>
 }
 
 impl View for HomeView {
@@ -25,30 +22,34 @@ impl View for HomeView {
             Message::OpenPicker => {
                 state.loading = true;
 
-                return Command::perform(async {
-                        let file = AsyncFileDialog::new()
-                            .add_filter("image", &["png", "jpg"])
-                            .pick_file()
-                            .await;
-                        file.unwrap().read().await
-                    }, |data| {
-                        let img = ImageReader::new(Cursor::new(data))
-                            .with_guessed_format()
-                            .unwrap()
-                            .decode()
-                            .unwrap()
-                            .into_rgba8();
-
-                        Message::Open(img)
-                    }
-                )
+                return Command::perform(services::file::open_image(), |result| Message::from_file(result, |(img, metadata)| Message::Open(img, metadata)));
+            }
+            Message::Open(data, metadata) => {
+                let mut view = EditingView::new(data);
+                view.metadata = metadata;
+                let command = Command::perform(services::image::histogram(view.image.clone()), Message::HistogramRecalculated);
+                *app = Fairplay::Editing(view);
+                return command;
+            }
+            Message::OpenProjectPicker => {
+                state.loading = true;
+                return EditingView::open_project_picker();
             }
-            Message::Open(data) => {
-                *app = Fairplay::Editing(EditingView::new(data.clone()));
-                return Command::perform(services::image::histogram(data), |r| Message::HistogramRecalculated(r));
+            Message::ProjectOpened(project) => {
+                return EditingView::open_project(app, project);
             }
             Message::Started => {
 
+            }
+            Message::Cancelled => {
+                state.loading = false;
+            }
+            Message::Failed(e) => {
+                state.loading = false;
+                state.notification = Some(e.to_string());
+            }
+            Message::NotificationDismissed => {
+                state.notification = None;
             }
             _ => {
                 panic!("Invalid message");
@@ -58,7 +59,7 @@ impl View for HomeView {
         Command::none()
     }
 
-    fn view(&self) -> Element<Message> {
+    fn view(&self) -> Element<'_, Message> {
         let open_btn = Button::new(
             Row::new()
                 .push(Text::new(String::from(icon_to_char(BootstrapIcon::FoldertwoOpen))).font(BOOTSTRAP_FONT))
@@ -68,9 +69,20 @@ impl View for HomeView {
             .on_press(Message::OpenPicker)
             .width(Length::Fill);
 
+        let open_project_btn = Button::new(
+            Row::new()
+                .push(Text::new(String::from(icon_to_char(BootstrapIcon::FileEarmarkImage))).font(BOOTSTRAP_FONT))
+                .push(Text::new("Open project"))
+                .spacing(10)
+        )
+            .on_press(Message::OpenProjectPicker)
+            .width(Length::Fill);
+
         let column = Container::new(
             Column::new()
                 .push(open_btn)
+                .push(open_project_btn)
+                .spacing(10)
                 .width(Length::Fixed(300_f32))
         )
             .width(Length::Fill)
@@ -83,8 +95,12 @@ impl View for HomeView {
             .height(Length::Fill)
             .align_items(Alignment::Center);
 
+        let content = Column::new()
+            .push_maybe(self.notification.clone().map(notification))
+            .push(row);
+
         if self.loading {
-            with_spinner(row)
-        } else { row.into() }
+            with_spinner(content)
+        } else { content.into() }
     }
 }
\ No newline at end of file
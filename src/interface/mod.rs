@@ -4,10 +4,12 @@ use crate::fairplay::{Fairplay, Message};
 pub mod home;
 pub mod editing;
 mod components;
-mod editing_components;
+pub mod editing_components;
+mod export;
 pub mod histogram;
+mod curves;
 
 pub trait View {
     fn update(app: &mut Fairplay, message: Message) -> Command<Message>;
-    fn view(&self) -> Element<Message>;
+    fn view(&self) -> Element<'_, Message>;
 }
\ No newline at end of file
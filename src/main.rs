@@ -7,8 +7,18 @@ mod update;
 mod view;
 mod services;
 mod models;
+#[cfg(not(target_arch = "wasm32"))]
+mod cli;
 
 
 pub fn main() -> iced::Result {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let mut args = std::env::args().skip(1);
+        if args.next().is_some_and(|a| a == "apply") {
+            std::process::exit(cli::run(args));
+        }
+    }
+
     Fairplay::run(Settings::default())
 }
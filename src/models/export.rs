@@ -0,0 +1,229 @@
+use std::fmt::{Display, Formatter};
+
+use image::codecs::png::{CompressionType, FilterType};
+use image::ImageFormat;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExportFormat {
+    Png,
+    Jpeg,
+    WebP,
+    Tiff,
+    Bmp,
+    Tga,
+    Qoi
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 7] = [
+        ExportFormat::Png,
+        ExportFormat::Jpeg,
+        ExportFormat::WebP,
+        ExportFormat::Tiff,
+        ExportFormat::Bmp,
+        ExportFormat::Tga,
+        ExportFormat::Qoi,
+    ];
+
+    pub fn image_format(self) -> ImageFormat {
+        match self {
+            ExportFormat::Png => { ImageFormat::Png }
+            ExportFormat::Jpeg => { ImageFormat::Jpeg }
+            ExportFormat::WebP => { ImageFormat::WebP }
+            ExportFormat::Tiff => { ImageFormat::Tiff }
+            ExportFormat::Bmp => { ImageFormat::Bmp }
+            ExportFormat::Tga => { ImageFormat::Tga }
+            ExportFormat::Qoi => { ImageFormat::Qoi }
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        self.image_format().extensions_str()[0]
+    }
+
+    pub fn supports_alpha(self) -> bool {
+        self != ExportFormat::Jpeg
+    }
+
+    pub fn supports_metadata(self) -> bool {
+        matches!(self, ExportFormat::Png | ExportFormat::Jpeg)
+    }
+
+    /// Whether an ICC profile can be embedded. Other formats are always written as sRGB.
+    pub fn supports_color_profile(self) -> bool {
+        matches!(self, ExportFormat::Png | ExportFormat::Jpeg)
+    }
+
+    pub fn supports_16_bit(self) -> bool {
+        matches!(self, ExportFormat::Png | ExportFormat::Tiff)
+    }
+}
+
+impl Display for ExportFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(
+            match self {
+                ExportFormat::Png => { "PNG" }
+                ExportFormat::Jpeg => { "JPEG" }
+                ExportFormat::WebP => { "WebP" }
+                ExportFormat::Tiff => { "TIFF" }
+                ExportFormat::Bmp => { "BMP" }
+                ExportFormat::Tga => { "TGA" }
+                ExportFormat::Qoi => { "QOI" }
+            }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PngCompression {
+    Fast,
+    Default,
+    Best
+}
+
+impl PngCompression {
+    pub const ALL: [PngCompression; 3] = [PngCompression::Fast, PngCompression::Default, PngCompression::Best];
+}
+
+impl From<PngCompression> for CompressionType {
+    fn from(value: PngCompression) -> Self {
+        match value {
+            PngCompression::Fast => { CompressionType::Fast }
+            PngCompression::Default => { CompressionType::Default }
+            PngCompression::Best => { CompressionType::Best }
+        }
+    }
+}
+
+impl Display for PngCompression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(
+            match self {
+                PngCompression::Fast => { "Fast" }
+                PngCompression::Default => { "Default" }
+                PngCompression::Best => { "Best" }
+            }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PngFilter {
+    None,
+    Sub,
+    Up,
+    Avg,
+    Paeth,
+    Adaptive
+}
+
+impl PngFilter {
+    pub const ALL: [PngFilter; 6] = [PngFilter::None, PngFilter::Sub, PngFilter::Up, PngFilter::Avg, PngFilter::Paeth, PngFilter::Adaptive];
+}
+
+impl From<PngFilter> for FilterType {
+    fn from(value: PngFilter) -> Self {
+        match value {
+            PngFilter::None => { FilterType::NoFilter }
+            PngFilter::Sub => { FilterType::Sub }
+            PngFilter::Up => { FilterType::Up }
+            PngFilter::Avg => { FilterType::Avg }
+            PngFilter::Paeth => { FilterType::Paeth }
+            PngFilter::Adaptive => { FilterType::Adaptive }
+        }
+    }
+}
+
+impl Display for PngFilter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(
+            match self {
+                PngFilter::None => { "None" }
+                PngFilter::Sub => { "Sub" }
+                PngFilter::Up => { "Up" }
+                PngFilter::Avg => { "Average" }
+                PngFilter::Paeth => { "Paeth" }
+                PngFilter::Adaptive => { "Adaptive" }
+            }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BitDepth {
+    Eight,
+    Sixteen
+}
+
+impl BitDepth {
+    pub const ALL: [BitDepth; 2] = [BitDepth::Eight, BitDepth::Sixteen];
+}
+
+impl Display for BitDepth {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BitDepth::Eight => { f.write_str("8 bit") }
+            BitDepth::Sixteen => { f.write_str("16 bit") }
+        }
+    }
+}
+
+/// Colour space of exported images. The modifiers work in sRGB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutputProfile {
+    Srgb,
+    DisplayP3,
+    AdobeRgb
+}
+
+impl OutputProfile {
+    pub const ALL: [OutputProfile; 3] = [OutputProfile::Srgb, OutputProfile::DisplayP3, OutputProfile::AdobeRgb];
+}
+
+impl Display for OutputProfile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(
+            match self {
+                OutputProfile::Srgb => { "sRGB" }
+                OutputProfile::DisplayP3 => { "Display P3" }
+                OutputProfile::AdobeRgb => { "Adobe RGB" }
+            }
+        )
+    }
+}
+
+/// Encoder settings of the export panel. They are stored between sessions, see
+/// `services::file::load_export_settings`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportSettings {
+    pub format: ExportFormat,
+    pub jpeg_quality: u8,
+    pub png_compression: PngCompression,
+    pub png_filter: PngFilter,
+    pub webp_lossless: bool,
+    pub webp_quality: u8,
+    pub bit_depth: BitDepth,
+    pub keep_alpha: bool,
+    pub keep_metadata: bool,
+    pub profile: OutputProfile
+}
+
+impl Default for ExportSettings {
+    fn default() -> Self {
+        Self {
+            format: ExportFormat::Png,
+            jpeg_quality: 90,
+            png_compression: PngCompression::Default,
+            png_filter: PngFilter::Adaptive,
+            webp_lossless: true,
+            webp_quality: 80,
+            bit_depth: BitDepth::Eight,
+            keep_alpha: true,
+            keep_metadata: true,
+            profile: OutputProfile::Srgb,
+        }
+    }
+}
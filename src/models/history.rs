@@ -1,12 +1,19 @@
+use std::fmt::{Display, Formatter};
+
+use fairplay_core::models::layer::Layer;
+use fairplay_core::models::modifier::Modifier;
 use undo::Action as UndoAction;
 use crate::interface::editing::EditingView;
-use crate::models::modifier::Modifier;
 
+#[allow(clippy::enum_variant_names)]
 pub enum Action {
     ModifierAdded(ModifierAdded),
     ModifierRemoved(ModifierRemoved),
     ModifierOptionsApplied(ModifierOptionsApplied),
-    ModifierSelected(ModifierSelected)
+    ModifierSelected(ModifierSelected),
+    ModifierMoved(ModifierMoved),
+    ModifierToggled(ModifierToggled),
+    SnapshotRestored(SnapshotRestored)
 }
 
 pub struct ModifierAdded {
@@ -25,7 +32,7 @@ impl ModifierAdded {
 
 pub struct ModifierRemoved {
     idx: usize,
-    modifier: Option<Modifier>,
+    layer: Option<Layer>,
     was_selected: bool
 }
 
@@ -33,20 +40,38 @@ impl ModifierRemoved {
     pub fn new(idx: usize) -> Self {
         ModifierRemoved {
             idx,
-            modifier: None,
+            layer: None,
             was_selected: false,
         }
     }
 }
 
+/// Applies the options of the selected modifier. The index and both the new and the old options
+/// are recorded when it is first applied, so that redoing doesn't depend on the selection.
 pub struct ModifierOptionsApplied {
-    previous: Option<Modifier>
+    applied: Option<AppliedOptions>
+}
+
+struct AppliedOptions {
+    idx: usize,
+    modifier: Modifier,
+    previous: Modifier
 }
 
 impl ModifierOptionsApplied {
     pub fn new() -> Self {
         Self {
-            previous: None,
+            applied: None,
+        }
+    }
+}
+
+/// Puts `modifier` at `idx`, and into the selection if that modifier is selected.
+fn set_modifier(target: &mut EditingView, idx: usize, modifier: &Modifier) {
+    target.modifiers[idx].modifier = modifier.clone();
+    if let Some((i, selected)) = &mut target.selected_modifier {
+        if *i == idx {
+            *selected = modifier.clone();
         }
     }
 }
@@ -67,6 +92,65 @@ impl ModifierSelected {
     }
 }
 
+pub struct ModifierMoved {
+    from: usize,
+    to: usize
+}
+
+impl ModifierMoved {
+    pub fn new(from: usize, to: usize) -> Self {
+        Self {
+            from,
+            to,
+        }
+    }
+}
+
+pub struct ModifierToggled {
+    idx: usize
+}
+
+impl ModifierToggled {
+    pub fn new(idx: usize) -> Self {
+        Self {
+            idx,
+        }
+    }
+}
+
+pub struct SnapshotRestored {
+    name: String,
+    modifiers: Vec<Layer>,
+    previous: Option<Vec<Layer>>,
+    previous_selected: Option<(usize, Modifier)>
+}
+
+impl SnapshotRestored {
+    pub fn new(name: String, modifiers: Vec<Layer>) -> Self {
+        Self {
+            name,
+            modifiers,
+            previous: None,
+            previous_selected: None,
+        }
+    }
+}
+
+fn move_modifier(target: &mut EditingView, from: usize, to: usize) {
+    let modifier = target.modifiers.remove(from);
+    target.modifiers.insert(to, modifier);
+
+    if let Some((i, _)) = &mut target.selected_modifier {
+        if *i == from {
+            *i = to;
+        } else if from < *i && *i <= to {
+            *i -= 1;
+        } else if to <= *i && *i < from {
+            *i += 1;
+        }
+    }
+}
+
 impl UndoAction for Action {
     type Target = EditingView;
     type Output = ();
@@ -74,23 +158,37 @@ impl UndoAction for Action {
     fn apply(&mut self, target: &mut Self::Target) -> Self::Output {
         match self {
             Action::ModifierAdded(data) => {
-                target.modifiers.push(data.modifier.clone());
+                target.modifiers.push(Layer::new(data.modifier.clone()));
                 data.previous_selected = target.selected_modifier.clone();
                 target.selected_modifier = Some((target.modifiers.len() - 1, data.modifier.clone()));
             }
             Action::ModifierRemoved(data) => {
-                if let Some((i, _)) = &target.selected_modifier {
+                if let Some((i, _)) = &mut target.selected_modifier {
                     if *i == data.idx {
                         data.was_selected = true;
                         target.selected_modifier = None;
+                    } else if *i > data.idx {
+                        *i -= 1;
                     }
                 }
-                data.modifier = Some(target.modifiers.remove(data.idx));
+                data.layer = Some(target.modifiers.remove(data.idx));
             }
             Action::ModifierOptionsApplied(data) => {
-                let selected = target.selected_modifier.clone().unwrap();
-                data.previous = Some(target.modifiers[selected.0].clone());
-                target.modifiers[selected.0] = selected.1;
+                let applied = data.applied.get_or_insert_with(|| {
+                    let (idx, modifier) = target.selected_modifier.clone().expect("Options are applied to the selected modifier");
+                    AppliedOptions { idx, previous: target.modifiers[idx].modifier.clone(), modifier }
+                });
+                set_modifier(target, applied.idx, &applied.modifier);
+            }
+            Action::ModifierMoved(data) => {
+                move_modifier(target, data.from, data.to);
+            }
+            Action::ModifierToggled(data) => {
+                target.modifiers[data.idx].enabled = !target.modifiers[data.idx].enabled;
+            }
+            Action::SnapshotRestored(data) => {
+                data.previous = Some(std::mem::replace(&mut target.modifiers, data.modifiers.clone()));
+                data.previous_selected = target.selected_modifier.take();
             }
             Action::ModifierSelected(data) => {
                 data.previous = target.selected_modifier.clone();
@@ -111,28 +209,343 @@ impl UndoAction for Action {
         match self {
             Action::ModifierAdded(data) => {
                 target.selected_modifier = data.previous_selected.clone();
-                data.modifier = target.modifiers.pop().unwrap()
+                data.modifier = target.modifiers.pop().unwrap().modifier
             }
             Action::ModifierRemoved(data) => {
-                target.modifiers.insert(data.idx, data.modifier.clone().unwrap());
+                let layer = data.layer.clone().unwrap();
+                target.modifiers.insert(data.idx, layer.clone());
+                if let Some((i, _)) = &mut target.selected_modifier {
+                    if *i >= data.idx {
+                        *i += 1;
+                    }
+                }
                 if data.was_selected {
-                    target.selected_modifier = Some((data.idx, data.modifier.clone().unwrap()));
+                    target.selected_modifier = Some((data.idx, layer.modifier));
                 }
                 data.was_selected = false;
             }
             Action::ModifierOptionsApplied(data) => {
-                let selected = target.selected_modifier.clone().unwrap();
-                target.modifiers[selected.0] = data.previous.clone().unwrap();
-                if let Some((idx, _)) = &target.selected_modifier {
-                    if *idx == selected.0 {
-                        target.selected_modifier = Some((*idx, data.previous.clone().unwrap()))
-                    }
-                }
-                data.previous = None;
+                let applied = data.applied.as_ref().expect("Undone after being applied");
+                set_modifier(target, applied.idx, &applied.previous);
             }
             Action::ModifierSelected(data) => {
                 target.selected_modifier = data.previous.clone();
             }
+            Action::ModifierMoved(data) => {
+                move_modifier(target, data.to, data.from);
+            }
+            Action::ModifierToggled(data) => {
+                target.modifiers[data.idx].enabled = !target.modifiers[data.idx].enabled;
+            }
+            Action::SnapshotRestored(data) => {
+                target.modifiers = data.previous.take().unwrap();
+                target.selected_modifier = data.previous_selected.take();
+            }
+        }
+    }
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Action::ModifierAdded(data) => { write!(f, "Add {}", data.modifier) }
+            Action::ModifierRemoved(data) => {
+                match &data.layer {
+                    Some(layer) => { write!(f, "Remove {}", layer.modifier) }
+                    None => { write!(f, "Remove modifier {}", data.idx + 1) }
+                }
+            }
+            Action::ModifierOptionsApplied(data) => {
+                match &data.applied {
+                    Some(applied) => { write!(f, "Change {} options", applied.previous) }
+                    None => { write!(f, "Change options") }
+                }
+            }
+            Action::ModifierSelected(data) => { write!(f, "Select {}", data.modifier) }
+            Action::ModifierMoved(data) => { write!(f, "Move modifier {} to {}", data.from + 1, data.to + 1) }
+            Action::ModifierToggled(data) => { write!(f, "Toggle modifier {}", data.idx + 1) }
+            Action::SnapshotRestored(data) => { write!(f, "Restore {}", data.name) }
+        }
+    }
+}
+
+/// Branching undo history.
+///
+/// Every state the document has been in is a node of a tree. Applying an action after an undo
+/// starts a new branch instead of discarding the redo stack, so any earlier state, abandoned
+/// branches included, can be returned to with [`History::go_to`].
+pub struct History<A> {
+    nodes: Vec<Node<A>>,
+    current: usize
+}
+
+struct Node<A> {
+    action: Option<A>,
+    label: String,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    /// The child `redo` moves to, the most recently visited one.
+    redo: Option<usize>
+}
+
+impl<A: UndoAction<Output = ()> + Display> Default for History<A> {
+    fn default() -> Self {
+        History::new()
+    }
+}
+
+/// A state in the history, as listed by [`History::entries`].
+pub struct HistoryEntry<'a> {
+    pub id: usize,
+    pub label: &'a str,
+    /// How many branches deep the state is, the main line being 0.
+    pub level: usize,
+    pub current: bool,
+    /// Whether the state is an ancestor of the current one (or the current one itself).
+    pub active: bool
+}
+
+impl<A: UndoAction<Output = ()> + Display> History<A> {
+    pub fn new() -> Self {
+        History {
+            nodes: vec![Node {
+                action: None,
+                label: String::from("Original"),
+                parent: None,
+                children: vec![],
+                redo: None,
+            }],
+            current: 0,
+        }
+    }
+
+    pub fn apply(&mut self, target: &mut A::Target, mut action: A) {
+        action.apply(target);
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+            label: action.to_string(),
+            action: Some(action),
+            parent: Some(self.current),
+            children: vec![],
+            redo: None,
+        });
+        let parent = &mut self.nodes[self.current];
+        parent.children.push(id);
+        parent.redo = Some(id);
+        self.current = id;
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.nodes[self.current].parent.is_some()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.nodes[self.current].redo.is_some()
+    }
+
+    pub fn undo(&mut self, target: &mut A::Target) {
+        let node = &mut self.nodes[self.current];
+        let Some(parent) = node.parent else { return };
+        node.action.as_mut().expect("Only the root has no action").undo(target);
+        self.current = parent;
+    }
+
+    pub fn redo(&mut self, target: &mut A::Target) {
+        let Some(child) = self.nodes[self.current].redo else { return };
+        self.nodes[child].action.as_mut().expect("Only the root has no action").redo(target);
+        self.current = child;
+    }
+
+    /// Moves to the state `id` by undoing up to the closest common ancestor and redoing down
+    /// the branch that leads to `id`.
+    pub fn go_to(&mut self, target: &mut A::Target, id: usize) {
+        if id >= self.nodes.len() {
+            return;
+        }
+
+        let path = self.path(id);
+        while !path.contains(&self.current) {
+            self.undo(target);
+        }
+
+        let start = path.iter().position(|n| *n == self.current).expect("Current state is on the path");
+        for &child in &path[start + 1..] {
+            self.nodes[self.current].redo = Some(child);
+            self.redo(target);
+        }
+    }
+
+    /// Runs `f` on `target` in the state `id` and comes back to the current state. Which branch
+    /// `redo` follows is left as it was.
+    pub fn inspect<T>(&mut self, target: &mut A::Target, id: usize, f: impl FnOnce(&A::Target) -> T) -> T {
+        let current = self.current;
+        let redo: Vec<Option<usize>> = self.nodes.iter().map(|n| n.redo).collect();
+        self.go_to(target, id);
+        let result = f(target);
+        self.go_to(target, current);
+        self.nodes.iter_mut().zip(redo).for_each(|(node, redo)| node.redo = redo);
+        result
+    }
+
+    /// Every state in depth-first order, so that each branch directly follows the state it
+    /// was started from.
+    pub fn entries(&self) -> Vec<HistoryEntry<'_>> {
+        let active = self.path(self.current);
+        let mut entries = Vec::with_capacity(self.nodes.len());
+        let mut stack = vec![(0, 0)];
+        while let Some((id, level)) = stack.pop() {
+            let node = &self.nodes[id];
+            entries.push(HistoryEntry {
+                id,
+                label: &node.label,
+                level,
+                current: id == self.current,
+                active: active.contains(&id),
+            });
+            // The first child continues the branch, later ones start new ones.
+            for (i, child) in node.children.iter().enumerate().rev() {
+                stack.push((*child, if i == 0 { level } else { level + 1 }));
+            }
+        }
+        entries
+    }
+
+    /// The states from the root down to `id`.
+    fn path(&self, mut id: usize) -> Vec<usize> {
+        let mut path = vec![id];
+        while let Some(parent) = self.nodes[id].parent {
+            path.push(parent);
+            id = parent;
+        }
+        path.reverse();
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fairplay_core::models::modifier::BoxBlurOptions;
+    use image::Rgba32FImage;
+
+    use super::*;
+
+    struct Push(char);
+
+    impl Display for Push {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            write!(f, "Push {}", self.0)
+        }
+    }
+
+    impl UndoAction for Push {
+        type Target = String;
+        type Output = ();
+
+        fn apply(&mut self, target: &mut String) {
+            target.push(self.0);
+        }
+
+        fn undo(&mut self, target: &mut String) {
+            target.pop();
         }
     }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut target = String::new();
+        let mut history = History::new();
+        history.apply(&mut target, Push('a'));
+        history.apply(&mut target, Push('b'));
+        history.undo(&mut target);
+        assert_eq!(target, "a");
+        assert!(history.can_redo());
+        history.redo(&mut target);
+        assert_eq!(target, "ab");
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn new_action_keeps_abandoned_branch() {
+        let mut target = String::new();
+        let mut history = History::new();
+        history.apply(&mut target, Push('a'));
+        history.apply(&mut target, Push('b'));
+        let ab = history.entries().last().unwrap().id;
+        history.undo(&mut target);
+        history.apply(&mut target, Push('c'));
+        history.apply(&mut target, Push('d'));
+        assert_eq!(target, "acd");
+
+        history.go_to(&mut target, ab);
+        assert_eq!(target, "ab");
+        history.go_to(&mut target, 0);
+        assert_eq!(target, "");
+        history.redo(&mut target);
+        history.redo(&mut target);
+        assert_eq!(target, "ab");
+    }
+
+    #[test]
+    fn inspecting_keeps_the_current_state() {
+        let mut target = String::new();
+        let mut history = History::new();
+        history.apply(&mut target, Push('a'));
+        history.apply(&mut target, Push('b'));
+        let ab = history.entries().last().unwrap().id;
+        history.undo(&mut target);
+        history.undo(&mut target);
+        history.apply(&mut target, Push('c'));
+
+        assert_eq!(history.inspect(&mut target, ab, |t| t.clone()), "ab");
+        assert_eq!(target, "c");
+        history.undo(&mut target);
+        history.redo(&mut target);
+        assert_eq!(target, "c");
+    }
+
+    #[test]
+    fn options_come_back_on_an_abandoned_branch() {
+        let blur = |size| Modifier::BoxBlur(BoxBlurOptions { size, ..BoxBlurOptions::default() });
+        let mut view = EditingView::new(Rgba32FImage::new(1, 1));
+        let mut history = History::new();
+        history.apply(&mut view, Action::ModifierAdded(ModifierAdded::new(blur(3))));
+        view.selected_modifier = Some((0, blur(7)));
+        history.apply(&mut view, Action::ModifierOptionsApplied(ModifierOptionsApplied::new()));
+        let applied = history.entries().last().unwrap().id;
+
+        history.undo(&mut view);
+        assert_eq!(view.modifiers[0].modifier, blur(3));
+        assert_eq!(view.selected_modifier, Some((0, blur(3))));
+
+        // Deselecting starts a new branch, so the selection is empty when going back.
+        history.apply(&mut view, Action::ModifierSelected(ModifierSelected::new(0, blur(3))));
+        assert_eq!(view.selected_modifier, None);
+        history.go_to(&mut view, applied);
+        assert_eq!(view.modifiers[0].modifier, blur(7));
+
+        let label = history.entries().iter().find(|e| e.id == applied).unwrap().label.to_string();
+        assert_eq!(label, "Change Box blur options");
+        history.undo(&mut view);
+        assert_eq!(history.entries().iter().find(|e| e.id == applied).unwrap().label, label);
+    }
+
+    #[test]
+    fn entries_list_branches_after_their_parent() {
+        let mut target = String::new();
+        let mut history = History::new();
+        history.apply(&mut target, Push('a'));
+        history.apply(&mut target, Push('b'));
+        history.undo(&mut target);
+        history.apply(&mut target, Push('c'));
+
+        let entries: Vec<_> = history.entries().iter()
+            .map(|e| (e.label.to_string(), e.level, e.current, e.active))
+            .collect();
+        assert_eq!(entries, vec![
+            ("Original".to_string(), 0, false, true),
+            ("Push a".to_string(), 0, false, true),
+            ("Push b".to_string(), 0, false, false),
+            ("Push c".to_string(), 1, true, true),
+        ]);
+    }
 }
@@ -0,0 +1,9 @@
+/// Metadata read from an opened image.
+#[derive(Debug, Clone, Default)]
+pub struct Metadata {
+    /// EXIF block (a TIFF structure) to embed on export. The orientation is already applied to
+    /// the image on open, so it is reset to normal here.
+    pub exif: Option<Vec<u8>>,
+    /// Key fields for the info panel, as name and value.
+    pub summary: Vec<(String, String)>
+}
@@ -1,2 +1,5 @@
-pub mod modifier;
-pub mod history;
\ No newline at end of file
+pub mod history;
+pub mod project;
+pub mod snapshot;
+pub mod export;
+pub mod metadata;
@@ -0,0 +1,207 @@
+use std::fmt::{Display, Formatter};
+use std::io::Cursor;
+
+use fairplay_core::models::layer::Layer;
+use fairplay_core::models::modifier::Modifier;
+use image::{DynamicImage, ImageError, ImageFormat, Rgba32FImage};
+use image::io::Reader as ImageReader;
+use serde::{Deserialize, Serialize};
+
+use crate::models::metadata::Metadata;
+
+/// A saved editing session: the untouched source image together with the modifier stack,
+/// so the edits stay non-destructive after reopening.
+#[derive(Debug, Clone)]
+pub struct Project {
+    pub image: Rgba32FImage,
+    pub modifiers: Vec<Layer>,
+    pub selected_modifier: Option<usize>,
+    pub histogram_visible: bool,
+    /// Metadata of the source image, so that exports keep it after reopening.
+    pub metadata: Metadata
+}
+
+/// On-disk layout of a `.fairplay` file. The source image is embedded as 16 bit PNG.
+///
+/// Version history:
+/// 1. Image, modifiers, selection and histogram visibility.
+/// 2. Adds `disabled`, the indices of modifiers that are switched off.
+/// 3. Adds `border` to neighbourhood modifiers. Older files keep the edge handling they were
+///    made with.
+/// 4. Adds the `custom-kernel`, `curves`, `levels` and `hue-saturation` modifiers.
+/// 5. Adds `exif` and `summary`, the metadata of the source image.
+#[derive(Serialize, Deserialize)]
+struct ProjectFile {
+    version: u32,
+    #[serde(with = "serde_bytes")]
+    image: Vec<u8>,
+    modifiers: Vec<Modifier>,
+    #[serde(default)]
+    disabled: Vec<usize>,
+    #[serde(default)]
+    selected_modifier: Option<usize>,
+    #[serde(default)]
+    histogram_visible: bool,
+    #[serde(default, with = "serde_bytes")]
+    exif: Option<Vec<u8>>,
+    #[serde(default)]
+    summary: Vec<(String, String)>
+}
+
+/// Just the version, read before the rest so that projects from newer versions are rejected
+/// instead of failing on modifiers this version doesn't know.
+#[derive(Deserialize)]
+struct ProjectVersion {
+    version: u32
+}
+
+#[derive(Debug)]
+pub enum ProjectError {
+    Format(ron::error::SpannedError),
+    Image(ImageError),
+    UnsupportedVersion(u32),
+    /// A modifier, counted from 1, with options it can't run with.
+    InvalidModifier(usize, String)
+}
+
+impl Display for ProjectError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProjectError::Format(e) => { write!(f, "Invalid project file: {}", e) }
+            ProjectError::Image(e) => { write!(f, "Invalid project image: {}", e) }
+            ProjectError::UnsupportedVersion(v) => {
+                write!(f, "Project version {} is not supported (latest is {})", v, Project::VERSION)
+            }
+            ProjectError::InvalidModifier(i, e) => { write!(f, "Invalid modifier {} in project: {}", i, e) }
+        }
+    }
+}
+
+impl std::error::Error for ProjectError {}
+
+impl Project {
+    pub const VERSION: u32 = 5;
+    pub const EXTENSION: &'static str = "fairplay";
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ProjectError> {
+        let mut image = Cursor::new(Vec::new());
+        DynamicImage::ImageRgba32F(self.image.clone())
+            .into_rgba16()
+            .write_to(&mut image, ImageFormat::Png)
+            .map_err(ProjectError::Image)?;
+
+        let file = ProjectFile {
+            version: Self::VERSION,
+            image: image.into_inner(),
+            modifiers: self.modifiers.iter().map(|l| l.modifier.clone()).collect(),
+            disabled: self.modifiers.iter().enumerate().filter(|(_, l)| !l.enabled).map(|(i, _)| i).collect(),
+            selected_modifier: self.selected_modifier,
+            histogram_visible: self.histogram_visible,
+            exif: self.metadata.exif.clone(),
+            summary: self.metadata.summary.clone(),
+        };
+        Ok(ron::to_string(&file).expect("Project is always serializable").into_bytes())
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, ProjectError> {
+        let version = ron::de::from_bytes::<ProjectVersion>(data).map_err(ProjectError::Format)?.version;
+        if version == 0 || version > Self::VERSION {
+            return Err(ProjectError::UnsupportedVersion(version));
+        }
+        let file: ProjectFile = ron::de::from_bytes(data).map_err(ProjectError::Format)?;
+
+        let mut modifiers = file.modifiers;
+        if file.version < 3 {
+            modifiers.iter_mut().for_each(Modifier::use_legacy_border);
+        }
+        for (i, modifier) in modifiers.iter().enumerate() {
+            modifier.validate().map_err(|e| ProjectError::InvalidModifier(i + 1, e))?;
+        }
+
+        let image = ImageReader::with_format(Cursor::new(file.image), ImageFormat::Png)
+            .decode()
+            .map_err(ProjectError::Image)?
+            .into_rgba32f();
+
+        let modifiers = modifiers.into_iter()
+            .enumerate()
+            .map(|(i, modifier)| Layer { modifier, enabled: !file.disabled.contains(&i) })
+            .collect::<Vec<_>>();
+
+        Ok(Project {
+            image,
+            selected_modifier: file.selected_modifier.filter(|i| *i < modifiers.len()),
+            modifiers,
+            histogram_visible: file.histogram_visible,
+            metadata: Metadata { exif: file.exif, summary: file.summary },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fairplay_core::models::border::BorderMode;
+    use fairplay_core::models::modifier::{BoxBlurOptions, GaussianBlurOptions, ThresholdingOptions};
+    use image::Rgba;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let project = Project {
+            image: Rgba32FImage::from_fn(7, 5, |x, y| Rgba([x as f32 / 7.0, y as f32 / 5.0, 0.5, 0.75])),
+            modifiers: vec![
+                Layer::new(Modifier::GaussianBlur(GaussianBlurOptions { size: 5, ..GaussianBlurOptions::default() })),
+                Layer { modifier: Modifier::Thresholding(ThresholdingOptions { grayscale: true, threshold: 10 }), enabled: false },
+            ],
+            selected_modifier: Some(1),
+            histogram_visible: true,
+            metadata: Metadata {
+                exif: Some(b"MM\0*\0\0\0\x08\0\0".to_vec()),
+                summary: vec![(String::from("Camera"), String::from("Fair Play 1"))],
+            },
+        };
+
+        let loaded = Project::from_bytes(&project.to_bytes().unwrap()).unwrap();
+        for (a, b) in loaded.image.pixels().zip(project.image.pixels()) {
+            for c in 0..4 {
+                assert!((a[c] - b[c]).abs() <= 0.5 / u16::MAX as f32, "{:?} != {:?}", a, b);
+            }
+        }
+        assert_eq!(loaded.modifiers, project.modifiers);
+        assert_eq!(loaded.selected_modifier, project.selected_modifier);
+        assert_eq!(loaded.histogram_visible, project.histogram_visible);
+        assert_eq!(loaded.metadata.exif, project.metadata.exif);
+        assert_eq!(loaded.metadata.summary, project.metadata.summary);
+    }
+
+    #[test]
+    fn version_2_keeps_its_edge_handling() {
+        let mut bytes = Project {
+            image: Rgba32FImage::new(2, 2),
+            modifiers: vec![Layer::new(Modifier::BoxBlur(BoxBlurOptions::default()))],
+            selected_modifier: None,
+            histogram_visible: false,
+            metadata: Metadata::default(),
+        }.to_bytes().unwrap();
+        let mut file: ProjectFile = ron::de::from_bytes(&bytes).unwrap();
+        file.version = 2;
+        bytes = ron::to_string(&file).unwrap().into_bytes();
+
+        let loaded = Project::from_bytes(&bytes).unwrap();
+        let Modifier::BoxBlur(opts) = &loaded.modifiers[0].modifier else { panic!("{:?}", loaded.modifiers) };
+        assert_eq!(opts.border.mode, BorderMode::Skip);
+    }
+
+    #[test]
+    fn newer_version_is_rejected() {
+        let data = br#"(version: 6, image: "", modifiers: [(type: "vignette", amount: 30)])"#;
+        assert!(matches!(Project::from_bytes(data), Err(ProjectError::UnsupportedVersion(6))));
+    }
+
+    #[test]
+    fn invalid_modifiers_are_rejected() {
+        let data = br#"(version: 5, image: "", modifiers: [(type: "gaussian-blur", size: 0)])"#;
+        assert!(matches!(Project::from_bytes(data), Err(ProjectError::InvalidModifier(1, _))));
+    }
+}
@@ -0,0 +1,9 @@
+use fairplay_core::models::layer::Layer;
+
+/// A named copy of the modifier stack, pinned so it can be restored or compared later.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub id: u64,
+    pub name: String,
+    pub modifiers: Vec<Layer>
+}
@@ -0,0 +1,487 @@
+use image::Rgba32FImage;
+use qcms::{DataType, Intent, Profile, Transform};
+
+use crate::models::export::{ExportFormat, OutputProfile};
+use crate::services::container;
+
+/// CIE xy chromaticities of the D65 and D50 white points.
+const D65: (f64, f64) = (0.3127, 0.3290);
+const D50_XYZ: [f64; 3] = [0.9642, 1.0, 0.8249];
+
+const BRADFORD: [[f64; 3]; 3] = [
+    [0.8951, 0.2664, -0.1614],
+    [-0.7502, 1.7135, 0.0367],
+    [0.0389, -0.0685, 1.0296],
+];
+
+enum Curve {
+    Srgb,
+    Gamma(f64),
+    /// Evenly spaced samples of the decoded values, from a `curv` tag.
+    Table(Vec<f64>),
+    /// ICC parametric curve: function type and its parameters `g, a, b, c, d, e, f`.
+    Parametric(u16, [f64; 7])
+}
+
+impl Curve {
+    fn decode(&self, v: f64) -> f64 {
+        match self {
+            Curve::Srgb => { if v <= 0.04045 { v / 12.92 } else { ((v + 0.055) / 1.055).powf(2.4) } }
+            Curve::Gamma(gamma) => { v.powf(*gamma) }
+            Curve::Table(table) => {
+                let position = v.clamp(0.0, 1.0) * (table.len() - 1) as f64;
+                let i = (position as usize).min(table.len() - 2);
+                table[i] + (table[i + 1] - table[i]) * (position - i as f64)
+            }
+            Curve::Parametric(function, [g, a, b, c, d, e, f]) => {
+                let power = |v: f64| (a * v + b).max(0.0).powf(*g);
+                match function {
+                    0 => { v.max(0.0).powf(*g) }
+                    1 => { if v >= -b / a { power(v) } else { 0.0 } }
+                    2 => { if v >= -b / a { power(v) + c } else { *c } }
+                    3 => { if v >= *d { power(v) } else { c * v } }
+                    _ => { if v >= *d { power(v) + e } else { c * v + f } }
+                }
+            }
+        }
+    }
+
+    fn encode(&self, v: f64) -> f64 {
+        match self {
+            Curve::Srgb => { if v <= 0.0031308 { v * 12.92 } else { 1.055 * v.powf(1.0 / 2.4) - 0.055 } }
+            Curve::Gamma(gamma) => { v.powf(1.0 / gamma) }
+            // Only used for input profiles, so inverted numerically.
+            Curve::Table(_) | Curve::Parametric(..) => {
+                let (mut low, mut high) = (0.0, 1.0);
+                for _ in 0..32 {
+                    let mid = (low + high) / 2.0;
+                    if self.decode(mid) < v { low = mid } else { high = mid }
+                }
+                (low + high) / 2.0
+            }
+        }
+    }
+}
+
+struct Space {
+    name: &'static str,
+    primaries: [(f64, f64); 3],
+    curve: Curve
+}
+
+impl OutputProfile {
+    fn space(self) -> Space {
+        match self {
+            OutputProfile::Srgb => {
+                Space { name: "sRGB", primaries: [(0.64, 0.33), (0.30, 0.60), (0.15, 0.06)], curve: Curve::Srgb }
+            }
+            OutputProfile::DisplayP3 => {
+                Space { name: "Display P3", primaries: [(0.680, 0.320), (0.265, 0.690), (0.150, 0.060)], curve: Curve::Srgb }
+            }
+            OutputProfile::AdobeRgb => {
+                Space { name: "Adobe RGB (1998)", primaries: [(0.64, 0.33), (0.21, 0.71), (0.15, 0.06)], curve: Curve::Gamma(563.0 / 256.0) }
+            }
+        }
+    }
+}
+
+/// What [`to_working_space`] did with an image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Conversion {
+    /// The profile can't be used (unparsable, or not RGB), the image is left as it is.
+    Unusable,
+    /// The profile describes sRGB already, the image is left as it is.
+    AlreadySrgb,
+    Converted
+}
+
+/// Converts `img` from the colour space described by the ICC profile `icc` into the sRGB
+/// working space used by the modifiers.
+pub fn to_working_space(img: &mut Rgba32FImage, icc: &[u8]) -> Conversion {
+    let Some(input) = Profile::new_from_slice(icc, false) else { return Conversion::Unusable };
+    if input.is_sRGB() {
+        return Conversion::AlreadySrgb;
+    }
+
+    // Matrix profiles are converted on the float values directly, like `from_working_space`.
+    if let Some((matrix, curves)) = matrix_and_curves(icc) {
+        let target = OutputProfile::Srgb.space();
+        let m = mul(invert(adapt_to_d50(rgb_to_xyz(target.primaries, D65))), matrix);
+        // Profiles of sRGB written by other software come through here, with their own
+        // rounding of the same primaries and curve.
+        let identity = (0..3).all(|i| (0..3).all(|j| (m[i][j] - if i == j { 1.0 } else { 0.0 }).abs() < 1e-3));
+        let srgb_curves = curves.iter().all(|c| (0..=16).all(|i| (c.decode(i as f64 / 16.0) - Curve::Srgb.decode(i as f64 / 16.0)).abs() < 1e-3));
+        if identity && srgb_curves {
+            return Conversion::AlreadySrgb;
+        }
+        for p in img.pixels_mut() {
+            let linear = [0, 1, 2].map(|i| curves[i].decode(p[i] as f64));
+            let converted = mul_vec(m, linear);
+            for (i, v) in converted.iter().enumerate() {
+                p[i] = target.curve.encode(v.clamp(0.0, 1.0)) as f32;
+            }
+        }
+        return Conversion::Converted;
+    }
+
+    let output = Profile::new_sRGB();
+    let Some(transform) = Transform::new(&input, &output, DataType::RGBA8, Intent::Perceptual) else { return Conversion::Unusable };
+
+    // LUT profiles go through qcms, which only converts 8 bit samples. The change it makes to
+    // the rounded image is added to the original values, so gradients finer than 8 bits survive.
+    let rounded = fairplay_core::to_rgba8(img);
+    let mut converted = rounded.clone();
+    transform.apply(converted.as_mut());
+    for ((p, r), c) in img.pixels_mut().zip(rounded.pixels()).zip(converted.pixels()) {
+        for i in 0..3 {
+            p[i] = (p[i] + (c[i] as f32 - r[i] as f32) / u8::MAX as f32).clamp(0.0, 1.0);
+        }
+    }
+    Conversion::Converted
+}
+
+/// Converts `img` from the sRGB working space into `profile`. All output profiles are matrix
+/// profiles, so this is done on the float values directly.
+pub fn from_working_space(img: &mut Rgba32FImage, profile: OutputProfile) {
+    if profile == OutputProfile::Srgb {
+        return;
+    }
+    let source = OutputProfile::Srgb.space();
+    let target = profile.space();
+    let m = mul(invert(rgb_to_xyz(target.primaries, D65)), rgb_to_xyz(source.primaries, D65));
+
+    for p in img.pixels_mut() {
+        let linear = [0, 1, 2].map(|i| source.curve.decode(p[i] as f64));
+        let converted = mul_vec(m, linear);
+        for (i, v) in converted.iter().enumerate() {
+            p[i] = target.curve.encode(v.clamp(0.0, 1.0)) as f32;
+        }
+    }
+}
+
+/// Embeds the ICC profile of `profile` into an encoded image. Formats without support are
+/// returned as they are.
+pub fn embed(data: Vec<u8>, format: ExportFormat, profile: OutputProfile) -> Vec<u8> {
+    const JPEG_HEADER: &[u8] = b"ICC_PROFILE\0";
+    let icc = icc_profile(profile);
+
+    let embedded = match format {
+        ExportFormat::Jpeg => {
+            // Profiles bigger than a segment are split, with a sequence number and the count.
+            let chunks: Vec<&[u8]> = icc.chunks(u16::MAX as usize - 2 - JPEG_HEADER.len() - 2).collect();
+            // Inserting puts every segment first, so go backwards to end up in order.
+            chunks.iter().enumerate().rev().try_fold(data.clone(), |data, (i, chunk)| {
+                let mut payload = JPEG_HEADER.to_vec();
+                payload.extend_from_slice(&[i as u8 + 1, chunks.len() as u8]);
+                payload.extend_from_slice(chunk);
+                container::insert_jpeg_segment(&data, 0xE2, &payload)
+            })
+        }
+        ExportFormat::Png => {
+            let mut payload = b"ICC profile\0\0".to_vec();
+            payload.extend_from_slice(&miniz_oxide::deflate::compress_to_vec_zlib(&icc, 6));
+            container::insert_png_chunk(&data, b"iCCP", &payload)
+        }
+        _ => { None }
+    };
+    embedded.unwrap_or(data)
+}
+
+/// The data of the tag with `signature` in an ICC profile.
+fn tag<'a>(icc: &'a [u8], signature: &[u8; 4]) -> Option<&'a [u8]> {
+    let u32_at = |at: usize| icc.get(at..at + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize);
+
+    let count = u32_at(128)?;
+    let (offset, size) = (0..count)
+        .map(|i| 132 + i * 12)
+        .find(|at| icc.get(*at..*at + 4) == Some(signature))
+        .map(|at| (u32_at(at + 4), u32_at(at + 8)))?;
+    icc.get(offset?..offset? + size?)
+}
+
+/// The D50 matrix to XYZ and the tone curves of an RGB matrix profile. `None` for profiles
+/// that describe the conversion with a LUT.
+fn matrix_and_curves(icc: &[u8]) -> Option<([[f64; 3]; 3], [Curve; 3])> {
+    if icc.get(16..20) != Some(b"RGB ") || tag(icc, b"A2B0").is_some() {
+        return None;
+    }
+
+    let fixed = |data: &[u8], at: usize| data.get(at..at + 4).map(|b| i32::from_be_bytes([b[0], b[1], b[2], b[3]]) as f64 / 65536.0);
+    let column = |signature: &[u8; 4]| {
+        let data = tag(icc, signature).filter(|data| data.starts_with(b"XYZ "))?;
+        Some([fixed(data, 8)?, fixed(data, 12)?, fixed(data, 16)?])
+    };
+    let curve = |signature: &[u8; 4]| {
+        let data = tag(icc, signature)?;
+        match data.get(..4)? {
+            b"curv" => {
+                let count = u32::from_be_bytes(data.get(8..12)?.try_into().ok()?) as usize;
+                let entries: Vec<u16> = data.get(12..12 + count * 2)?
+                    .chunks_exact(2)
+                    .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                    .collect();
+                Some(match entries.as_slice() {
+                    [] => { Curve::Gamma(1.0) }
+                    [gamma] => { Curve::Gamma(*gamma as f64 / 256.0) }
+                    table => { Curve::Table(table.iter().map(|v| *v as f64 / u16::MAX as f64).collect()) }
+                })
+            }
+            b"para" => {
+                let function = u16::from_be_bytes(data.get(8..10)?.try_into().ok()?);
+                let count = [1, 3, 4, 5, 7].get(function as usize)?;
+                let mut params = [1.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0];
+                for (i, param) in params.iter_mut().take(*count).enumerate() {
+                    *param = fixed(data, 12 + i * 4)?;
+                }
+                Some(Curve::Parametric(function, params))
+            }
+            _ => { None }
+        }
+    };
+
+    let [r, g, b] = [column(b"rXYZ")?, column(b"gXYZ")?, column(b"bXYZ")?];
+    let matrix = std::array::from_fn(|i| [r[i], g[i], b[i]]);
+    Some((matrix, [curve(b"rTRC")?, curve(b"gTRC")?, curve(b"bTRC")?]))
+}
+
+/// The description stored in an ICC profile, if it has a readable one.
+pub fn description(icc: &[u8]) -> Option<String> {
+    let tag = tag(icc, b"desc")?;
+
+    let text = match tag.get(..4)? {
+        b"desc" => {
+            let len = u32::from_be_bytes(tag.get(8..12)?.try_into().ok()?) as usize;
+            String::from_utf8_lossy(tag.get(12..12 + len)?).to_string()
+        }
+        b"mluc" => {
+            // The first localized record.
+            let len = u32::from_be_bytes(tag.get(20..24)?.try_into().ok()?) as usize;
+            let start = u32::from_be_bytes(tag.get(24..28)?.try_into().ok()?) as usize;
+            let units: Vec<u16> = tag.get(start..start + len)?
+                .chunks_exact(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => { return None }
+    };
+
+    let text = text.trim_end_matches('\0').trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+/// Builds an ICC v2 display profile with a matrix and tone curves for `profile`.
+pub fn icc_profile(profile: OutputProfile) -> Vec<u8> {
+    let space = profile.space();
+    let matrix = adapt_to_d50(rgb_to_xyz(space.primaries, D65));
+
+    let curve = match &space.curve {
+        Curve::Gamma(gamma) => { curv(&[(gamma * 256.0).round() as u16]) }
+        curve => {
+            let table: Vec<u16> = (0..1024)
+                .map(|i| (curve.decode(i as f64 / 1023.0) * 65535.0).round() as u16)
+                .collect();
+            curv(&table)
+        }
+    };
+
+    let column = |i: usize| xyz([matrix[0][i], matrix[1][i], matrix[2][i]]);
+    let tags: Vec<(&[u8; 4], Vec<u8>)> = vec![
+        (b"desc", desc(space.name)),
+        (b"cprt", text("No copyright, use freely")),
+        (b"wtpt", xyz(D50_XYZ)),
+        (b"rXYZ", column(0)),
+        (b"gXYZ", column(1)),
+        (b"bXYZ", column(2)),
+        (b"rTRC", curve.clone()),
+        (b"gTRC", curve.clone()),
+        (b"bTRC", curve),
+    ];
+
+    let mut offset = 128 + 4 + tags.len() * 12;
+    let mut table = (tags.len() as u32).to_be_bytes().to_vec();
+    let mut data = vec![];
+    for (signature, tag) in &tags {
+        table.extend_from_slice(*signature);
+        table.extend_from_slice(&(offset as u32).to_be_bytes());
+        table.extend_from_slice(&(tag.len() as u32).to_be_bytes());
+        data.extend_from_slice(tag);
+        // Tags start on 4 byte boundaries.
+        let padding = (4 - tag.len() % 4) % 4;
+        data.resize(data.len() + padding, 0);
+        offset += tag.len() + padding;
+    }
+
+    let mut header = vec![0u8; 128];
+    header[0..4].copy_from_slice(&(offset as u32).to_be_bytes());
+    header[8..12].copy_from_slice(&[0x02, 0x10, 0x00, 0x00]);
+    header[12..16].copy_from_slice(b"mntr");
+    header[16..20].copy_from_slice(b"RGB ");
+    header[20..24].copy_from_slice(b"XYZ ");
+    header[36..40].copy_from_slice(b"acsp");
+    header[68..80].copy_from_slice(&xyz(D50_XYZ)[8..]);
+
+    [header, table, data].concat()
+}
+
+fn s15_fixed16(v: f64) -> [u8; 4] {
+    ((v * 65536.0).round() as i32).to_be_bytes()
+}
+
+fn xyz(v: [f64; 3]) -> Vec<u8> {
+    let mut tag = b"XYZ \0\0\0\0".to_vec();
+    v.iter().for_each(|c| tag.extend_from_slice(&s15_fixed16(*c)));
+    tag
+}
+
+fn curv(table: &[u16]) -> Vec<u8> {
+    let mut tag = b"curv\0\0\0\0".to_vec();
+    tag.extend_from_slice(&(table.len() as u32).to_be_bytes());
+    table.iter().for_each(|v| tag.extend_from_slice(&v.to_be_bytes()));
+    tag
+}
+
+fn text(text: &str) -> Vec<u8> {
+    [b"text\0\0\0\0", text.as_bytes(), b"\0"].concat()
+}
+
+fn desc(text: &str) -> Vec<u8> {
+    let mut tag = b"desc\0\0\0\0".to_vec();
+    tag.extend_from_slice(&(text.len() as u32 + 1).to_be_bytes());
+    tag.extend_from_slice(text.as_bytes());
+    tag.push(0);
+    // Empty Unicode and ScriptCode descriptions.
+    tag.extend_from_slice(&[0; 4 + 4 + 2 + 1 + 67]);
+    tag
+}
+
+/// Matrix from linear RGB to XYZ for the given primaries and white point.
+fn rgb_to_xyz(primaries: [(f64, f64); 3], white: (f64, f64)) -> [[f64; 3]; 3] {
+    let to_xyz = |(x, y): (f64, f64)| [x / y, 1.0, (1.0 - x - y) / y];
+    let columns = primaries.map(to_xyz);
+    let m = [
+        [columns[0][0], columns[1][0], columns[2][0]],
+        [columns[0][1], columns[1][1], columns[2][1]],
+        [columns[0][2], columns[1][2], columns[2][2]],
+    ];
+    let s = mul_vec(invert(m), to_xyz(white));
+    m.map(|row| [row[0] * s[0], row[1] * s[1], row[2] * s[2]])
+}
+
+/// Bradford chromatic adaptation of a D65 matrix to the D50 connection space.
+fn adapt_to_d50(m: [[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    let source = mul_vec(BRADFORD, mul_vec(m, [1.0, 1.0, 1.0]));
+    let target = mul_vec(BRADFORD, D50_XYZ);
+    let scale = [
+        [target[0] / source[0], 0.0, 0.0],
+        [0.0, target[1] / source[1], 0.0],
+        [0.0, 0.0, target[2] / source[2]],
+    ];
+    mul(mul(invert(BRADFORD), mul(scale, BRADFORD)), m)
+}
+
+fn mul(a: [[f64; 3]; 3], b: [[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    std::array::from_fn(|i| std::array::from_fn(|j| (0..3).map(|k| a[i][k] * b[k][j]).sum()))
+}
+
+fn mul_vec(m: [[f64; 3]; 3], v: [f64; 3]) -> [f64; 3] {
+    std::array::from_fn(|i| (0..3).map(|k| m[i][k] * v[k]).sum())
+}
+
+fn invert(m: [[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    let cofactor = |i: usize, j: usize| {
+        let (r1, r2) = ((i + 1) % 3, (i + 2) % 3);
+        let (c1, c2) = ((j + 1) % 3, (j + 2) % 3);
+        m[r1][c1] * m[r2][c2] - m[r1][c2] * m[r2][c1]
+    };
+    let det: f64 = (0..3).map(|j| m[0][j] * cofactor(0, j)).sum();
+    std::array::from_fn(|i| std::array::from_fn(|j| cofactor(j, i) / det))
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use super::*;
+
+    #[test]
+    fn srgb_matrix_matches_the_standard() {
+        let m = adapt_to_d50(rgb_to_xyz(OutputProfile::Srgb.space().primaries, D65));
+        // The D50 adapted sRGB primaries published by the ICC.
+        let expected = [[0.4360, 0.3851, 0.1431], [0.2225, 0.7169, 0.0606], [0.0139, 0.0971, 0.7141]];
+        for i in 0..3 {
+            for j in 0..3 {
+                assert!((m[i][j] - expected[i][j]).abs() < 0.001, "{:?}", m);
+            }
+        }
+    }
+
+    #[test]
+    fn generated_profiles_round_trip() {
+        let img = fairplay_core::from_rgba8(&RgbaImage::from_fn(16, 16, |x, y| Rgba([x as u8 * 17, y as u8 * 17, 128, 200])));
+        for profile in OutputProfile::ALL {
+            let icc = icc_profile(profile);
+            assert_eq!(description(&icc).as_deref(), Some(profile.space().name));
+
+            let mut converted = img.clone();
+            from_working_space(&mut converted, profile);
+            let expected = if profile == OutputProfile::Srgb { Conversion::AlreadySrgb } else { Conversion::Converted };
+            assert_eq!(to_working_space(&mut converted, &icc), expected);
+            for (a, b) in converted.pixels().zip(img.pixels()) {
+                for c in 0..3 {
+                    assert!((a[c] - b[c]).abs() < 1e-3, "{:?}: {:?} != {:?}", profile, a, b);
+                }
+                assert_eq!(a[3], b[3]);
+            }
+        }
+    }
+
+    #[test]
+    fn parametric_curves_are_read() {
+        // sRGB as a type 3 parametric curve.
+        let mut para = b"para\0\0\0\0\0\x03\0\0".to_vec();
+        for v in [2.4, 1.0 / 1.055, 0.055 / 1.055, 1.0 / 12.92, 0.04045] {
+            para.extend_from_slice(&s15_fixed16(v));
+        }
+        let mut icc = icc_profile(OutputProfile::Srgb);
+        let offset = icc.len();
+        icc.extend_from_slice(&para);
+        // Points the red curve at the new tag, which comes after the green and blue ones.
+        let entry = 132 + 6 * 12;
+        assert_eq!(&icc[entry..entry + 4], b"rTRC");
+        icc[entry + 4..entry + 8].copy_from_slice(&(offset as u32).to_be_bytes());
+        icc[entry + 8..entry + 12].copy_from_slice(&(para.len() as u32).to_be_bytes());
+
+        let (_, [red, green, _]) = matrix_and_curves(&icc).unwrap();
+        assert!(matches!(red, Curve::Parametric(3, _)));
+        for v in [0.0, 0.02, 0.2, 0.5, 0.9, 1.0] {
+            assert!((red.decode(v) - Curve::Srgb.decode(v)).abs() < 1e-4, "{}", v);
+            assert!((green.decode(v) - Curve::Srgb.decode(v)).abs() < 1e-4, "{}", v);
+            assert!((red.encode(red.decode(v)) - v).abs() < 1e-4, "{}", v);
+        }
+    }
+
+    #[test]
+    fn conversion_keeps_16_bit_gradients() {
+        let mut img = Rgba32FImage::from_fn(1024, 1, |x, _| Rgba([0.5 + x as f32 / 8192.0, 0.5, 0.5, 1.0]));
+        assert_eq!(to_working_space(&mut img, &icc_profile(OutputProfile::DisplayP3)), Conversion::Converted);
+        let reds: Vec<f32> = img.pixels().map(|p| p[0]).collect();
+        assert!(reds.windows(2).all(|w| w[0] < w[1]), "{:?}", reds);
+    }
+
+    #[test]
+    fn wide_gamut_values_are_converted() {
+        let mut img = Rgba32FImage::from_pixel(1, 1, Rgba([1.0, 0.0, 0.0, 1.0]));
+        from_working_space(&mut img, OutputProfile::DisplayP3);
+        let p = img.get_pixel(0, 0);
+        // sRGB red is inside P3, so it isn't fully saturated there.
+        assert!(p[0] < 1.0 && p[1] > 0.0, "{:?}", p);
+    }
+
+    #[test]
+    fn unusable_profiles_are_ignored() {
+        let mut img = Rgba32FImage::new(1, 1);
+        assert_eq!(to_working_space(&mut img, b"not a profile"), Conversion::Unusable);
+        assert_eq!(description(b"short"), None);
+    }
+}
@@ -0,0 +1,75 @@
+//! Inserting metadata blocks into already encoded JPEG and PNG files, for data the `image`
+//! encoders can't write themselves.
+
+/// Inserts an APPn segment with `payload` after the SOI marker and the JFIF header, which
+/// has to stay first. Returns `None` if the payload doesn't fit into a segment.
+pub fn insert_jpeg_segment(data: &[u8], marker: u8, payload: &[u8]) -> Option<Vec<u8>> {
+    let len = 2 + payload.len();
+    if len > u16::MAX as usize || data.len() < 2 {
+        return None;
+    }
+
+    let mut at = 2;
+    if data.get(2..4) == Some(&[0xFF, 0xE0]) {
+        let app0 = u16::from_be_bytes([*data.get(4)?, *data.get(5)?]) as usize;
+        at += 2 + app0;
+    }
+    let at = at.min(data.len());
+
+    let mut out = Vec::with_capacity(data.len() + len + 2);
+    out.extend_from_slice(&data[..at]);
+    out.extend_from_slice(&[0xFF, marker]);
+    out.extend_from_slice(&(len as u16).to_be_bytes());
+    out.extend_from_slice(payload);
+    out.extend_from_slice(&data[at..]);
+    Some(out)
+}
+
+/// Inserts a chunk right after the IHDR chunk.
+pub fn insert_png_chunk(data: &[u8], kind: &[u8; 4], payload: &[u8]) -> Option<Vec<u8>> {
+    // Signature and IHDR chunk.
+    const HEADER_LEN: usize = 8 + 25;
+    if data.len() < HEADER_LEN {
+        return None;
+    }
+
+    let mut chunk = kind.to_vec();
+    chunk.extend_from_slice(payload);
+
+    let mut out = Vec::with_capacity(data.len() + chunk.len() + 8);
+    out.extend_from_slice(&data[..HEADER_LEN]);
+    out.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    out.extend_from_slice(&chunk);
+    out.extend_from_slice(&crc32(&chunk).to_be_bytes());
+    out.extend_from_slice(&data[HEADER_LEN..]);
+    Some(out)
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc_matches_png_spec() {
+        // The CRC of an empty IEND chunk.
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+    }
+
+    #[test]
+    fn jpeg_segment_goes_after_jfif_header() {
+        let jpeg = [0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0xAA, 0xBB, 0xFF, 0xD9];
+        let out = insert_jpeg_segment(&jpeg, 0xE1, &[1, 2]).unwrap();
+        assert_eq!(out, [0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0xAA, 0xBB, 0xFF, 0xE1, 0x00, 0x04, 1, 2, 0xFF, 0xD9]);
+    }
+}
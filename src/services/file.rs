@@ -0,0 +1,339 @@
+use std::fmt::{Display, Formatter};
+use std::io::Cursor;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use fairplay_core::models::layer::Layer;
+use fairplay_core::models::pipeline::{Pipeline, PipelineFormat};
+use fairplay_core::StageCache;
+use image::{DynamicImage, ImageDecoder, ImageError, ImageFormat, Rgba32FImage};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::io::Reader as ImageReader;
+use rfd::AsyncFileDialog;
+
+use crate::models::export::{BitDepth, ExportFormat, ExportSettings};
+use crate::models::metadata::Metadata;
+use crate::models::project::{Project, ProjectError};
+use crate::services;
+use crate::services::color::Conversion;
+
+/// Formats that can be opened, with the names shown in the file dialog filters.
+///
+/// AVIF is left out: the `image` crate only encodes it unless built with the native decoder.
+pub const READABLE_FORMATS: [(&str, ImageFormat); 13] = [
+    ("PNG", ImageFormat::Png),
+    ("JPEG", ImageFormat::Jpeg),
+    ("TIFF", ImageFormat::Tiff),
+    ("WebP", ImageFormat::WebP),
+    ("BMP", ImageFormat::Bmp),
+    ("GIF", ImageFormat::Gif),
+    ("TGA", ImageFormat::Tga),
+    ("PNM", ImageFormat::Pnm),
+    ("QOI", ImageFormat::Qoi),
+    ("ICO", ImageFormat::Ico),
+    ("Radiance HDR", ImageFormat::Hdr),
+    ("OpenEXR", ImageFormat::OpenExr),
+    ("Farbfeld", ImageFormat::Farbfeld),
+];
+
+/// Failure of one of the open or save operations below.
+#[derive(Debug)]
+pub enum FileError {
+    Decode(ImageError),
+    UnknownFormat(String),
+    Encode(ImageError),
+    Settings(ron::Error),
+    Write(std::io::Error),
+    Project(ProjectError)
+}
+
+impl Display for FileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileError::Decode(e) => { write!(f, "Failed to open the image: {}", e) }
+            FileError::UnknownFormat(name) => { write!(f, "Cannot tell the image format of '{}' from its extension", name) }
+            FileError::Encode(e) => { write!(f, "Failed to encode the image: {}", e) }
+            FileError::Settings(e) => { write!(f, "Failed to store the export settings: {}", e) }
+            FileError::Write(e) => { write!(f, "Failed to write the file: {}", e) }
+            FileError::Project(e) => { write!(f, "{}", e) }
+        }
+    }
+}
+
+impl std::error::Error for FileError {}
+
+/// Asks for an image and decodes it. `None` means the dialog was cancelled.
+pub async fn open_image() -> Result<Option<(Rgba32FImage, Metadata)>, FileError> {
+    let all: Vec<&str> = READABLE_FORMATS.iter()
+        .flat_map(|(_, format)| format.extensions_str())
+        .copied()
+        .collect();
+    let mut dialog = AsyncFileDialog::new().add_filter("All images", &all);
+    for (name, format) in READABLE_FORMATS {
+        dialog = dialog.add_filter(name, format.extensions_str());
+    }
+    let Some(file) = dialog.pick_file().await else { return Ok(None) };
+
+    decode(&file.file_name(), file.read().await).map(Some)
+}
+
+/// Decodes an image, detecting the format from the contents and falling back to the extension
+/// of `name` for formats without a signature (TGA). The EXIF orientation is applied, so the
+/// result is upright. 16 bit and float images keep their precision.
+pub fn decode(name: &str, data: Vec<u8>) -> Result<(Rgba32FImage, Metadata), FileError> {
+    let (mut metadata, orientation) = services::metadata::read(&data);
+    let mut reader = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|e| FileError::Decode(ImageError::IoError(e)))?;
+    if reader.format().is_none() {
+        let format = ImageFormat::from_path(name).map_err(|_| FileError::UnknownFormat(name.to_string()))?;
+        reader.set_format(format);
+    }
+
+    let mut decoder = reader.into_decoder().map_err(FileError::Decode)?;
+    let icc = decoder.icc_profile().ok().flatten();
+    let img = DynamicImage::from_decoder(decoder).map_err(FileError::Decode)?;
+    let mut img = services::metadata::orient(img, orientation).into_rgba32f();
+    if let Some(icc) = icc {
+        let name = || services::color::description(&icc).unwrap_or_else(|| String::from("Embedded"));
+        let profile = match services::color::to_working_space(&mut img, &icc) {
+            Conversion::Unusable => { None }
+            Conversion::AlreadySrgb => { Some(name()) }
+            Conversion::Converted => { Some(format!("{} (converted to sRGB)", name())) }
+        };
+        if let Some(profile) = profile {
+            metadata.summary.push((String::from("Colour profile"), profile));
+        }
+    }
+
+    Ok((img, metadata))
+}
+
+pub async fn open_project() -> Result<Option<Project>, FileError> {
+    let Some(file) = AsyncFileDialog::new()
+        .add_filter("Fairplay project", &[Project::EXTENSION])
+        .pick_file()
+        .await else { return Ok(None) };
+
+    Project::from_bytes(&file.read().await)
+        .map(Some)
+        .map_err(FileError::Project)
+}
+
+/// Asks where to save and writes the fully rendered image there, encoded with `settings`.
+/// `exif` is embedded when the settings ask for it and the format can hold it. Cached stages
+/// are reused but the cache is left as it is.
+pub async fn save_image(cache: Arc<Mutex<StageCache>>, layers: Vec<Layer>, settings: ExportSettings, exif: Option<Vec<u8>>) -> Result<(), FileError> {
+    let extension = settings.format.extension();
+    let Some(handle) = AsyncFileDialog::new()
+        .add_filter(settings.format.to_string(), settings.format.image_format().extensions_str())
+        .set_file_name(format!("edited.{}", extension))
+        .save_file()
+        .await else { return Ok(()) };
+
+    let img = cache.lock().unwrap().output_of(&layers);
+    let mut data = encode(Arc::unwrap_or_clone(img), &settings)?;
+    if let Some(exif) = exif.filter(|_| settings.keep_metadata) {
+        data = services::metadata::embed(data, settings.format, &exif);
+    }
+    handle.write(&data).await.map_err(FileError::Write)
+}
+
+/// Encodes `img` with the format and encoder options of `settings`. Alpha, 16 bit samples and
+/// colour profiles other than sRGB are only written when the format supports them.
+pub fn encode(mut img: Rgba32FImage, settings: &ExportSettings) -> Result<Vec<u8>, FileError> {
+    let format = settings.format;
+    let profile = format.supports_color_profile().then_some(settings.profile);
+    if let Some(profile) = profile {
+        services::color::from_working_space(&mut img, profile);
+    }
+    let alpha = settings.keep_alpha && format.supports_alpha();
+    let wide = settings.bit_depth == BitDepth::Sixteen && format.supports_16_bit();
+    let img = DynamicImage::ImageRgba32F(img);
+    let img = match (alpha, wide) {
+        (true, false) => { DynamicImage::ImageRgba8(img.into_rgba8()) }
+        (false, false) => { DynamicImage::ImageRgb8(img.into_rgb8()) }
+        (true, true) => { DynamicImage::ImageRgba16(img.into_rgba16()) }
+        (false, true) => { DynamicImage::ImageRgb16(img.into_rgb16()) }
+    };
+
+    let mut data = Cursor::new(Vec::new());
+    match format {
+        ExportFormat::Png => {
+            img.write_with_encoder(PngEncoder::new_with_quality(&mut data, settings.png_compression.into(), settings.png_filter.into()))
+        }
+        ExportFormat::Jpeg => {
+            img.write_with_encoder(JpegEncoder::new_with_quality(&mut data, settings.jpeg_quality))
+        }
+        // The image crate only writes lossless WebP, lossy files go through libwebp.
+        #[cfg(not(target_arch = "wasm32"))]
+        ExportFormat::WebP if !settings.webp_lossless => {
+            let encoder = match &img {
+                DynamicImage::ImageRgb8(rgb) => { webp::Encoder::from_rgb(rgb, rgb.width(), rgb.height()) }
+                _ => { webp::Encoder::from_rgba(img.as_bytes(), img.width(), img.height()) }
+            };
+            return Ok(encoder.encode(settings.webp_quality as f32).to_vec());
+        }
+        _ => {
+            img.write_to(&mut data, format.image_format())
+        }
+    }.map_err(FileError::Encode)?;
+
+    let data = data.into_inner();
+    Ok(match profile {
+        Some(profile) => { services::color::embed(data, format, profile) }
+        None => { data }
+    })
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn export_settings_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("fairplay").join("export.ron"))
+}
+
+/// The export settings used last time, or the defaults if there are none.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_export_settings() -> ExportSettings {
+    export_settings_path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|contents| ron::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn load_export_settings() -> ExportSettings {
+    ExportSettings::default()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn store_export_settings(settings: &ExportSettings) -> Result<(), FileError> {
+    let Some(path) = export_settings_path() else { return Ok(()) };
+    let contents = ron::to_string(settings).map_err(FileError::Settings)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(FileError::Write)?;
+    }
+    std::fs::write(path, contents).map_err(FileError::Write)
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn store_export_settings(_settings: &ExportSettings) -> Result<(), FileError> {
+    Ok(())
+}
+
+pub async fn save_pipeline(pipeline: Pipeline) -> Result<(), FileError> {
+    let Some(handle) = AsyncFileDialog::new()
+        .add_filter("pipeline", &PipelineFormat::EXTENSIONS)
+        .set_file_name("pipeline.json")
+        .save_file()
+        .await else { return Ok(()) };
+
+    let contents = pipeline.serialize(PipelineFormat::from_file_name(&handle.file_name()));
+    handle.write(contents.as_bytes()).await.map_err(FileError::Write)
+}
+
+pub async fn save_project(project: Project) -> Result<(), FileError> {
+    let Some(handle) = AsyncFileDialog::new()
+        .add_filter("Fairplay project", &[Project::EXTENSION])
+        .set_file_name(format!("project.{}", Project::EXTENSION))
+        .save_file()
+        .await else { return Ok(()) };
+
+    let data = project.to_bytes().map_err(FileError::Project)?;
+    handle.write(&data).await.map_err(FileError::Write)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{ImageBuffer, Rgba, RgbaImage};
+
+    use crate::models::export::OutputProfile;
+
+    use super::*;
+
+    fn encoded(format: ImageFormat) -> (RgbaImage, Vec<u8>) {
+        let img = RgbaImage::from_fn(4, 3, |x, y| Rgba([x as u8 * 60, y as u8 * 80, 10, 255]));
+        let mut data = Cursor::new(vec![]);
+        img.write_to(&mut data, format).unwrap();
+        (img, data.into_inner())
+    }
+
+    #[test]
+    fn detects_format_from_contents() {
+        for format in [ImageFormat::Png, ImageFormat::Tiff, ImageFormat::Bmp, ImageFormat::Qoi] {
+            let (img, data) = encoded(format);
+            assert_eq!(fairplay_core::to_rgba8(&decode("no-extension", data).unwrap().0), img);
+        }
+    }
+
+    #[test]
+    fn falls_back_to_extension() {
+        let (img, data) = encoded(ImageFormat::Tga);
+        assert_eq!(fairplay_core::to_rgba8(&decode("picture.TGA", data.clone()).unwrap().0), img);
+        assert!(matches!(decode("picture", data), Err(FileError::UnknownFormat(_))));
+    }
+
+    #[test]
+    fn encodes_with_settings() {
+        let img = RgbaImage::from_fn(4, 3, |x, y| Rgba([x as u8 * 60, y as u8 * 80, 10, 128]));
+        let source = fairplay_core::from_rgba8(&img);
+        for format in ExportFormat::ALL {
+            for bit_depth in BitDepth::ALL {
+                let settings = ExportSettings { format, bit_depth, keep_alpha: false, ..ExportSettings::default() };
+                let data = encode(source.clone(), &settings).unwrap();
+                let decoded = image::load_from_memory_with_format(&data, format.image_format()).unwrap();
+                assert!(!decoded.color().has_alpha(), "{}", format);
+                assert_eq!(decoded.color().bytes_per_pixel() > 4, bit_depth == BitDepth::Sixteen && format.supports_16_bit());
+            }
+        }
+
+        let settings = ExportSettings { format: ExportFormat::Png, bit_depth: BitDepth::Sixteen, ..ExportSettings::default() };
+        let decoded = image::load_from_memory(&encode(source, &settings).unwrap()).unwrap();
+        assert_eq!(decoded.into_rgba8(), img);
+    }
+
+    #[test]
+    fn keeps_16_bit_precision() {
+        let img: ImageBuffer<Rgba<u16>, _> = ImageBuffer::from_fn(64, 2, |x, y| Rgba([x as u16 * 1001, y as u16 * 7, 40000, 65535]));
+        let mut data = Cursor::new(vec![]);
+        img.write_to(&mut data, ImageFormat::Png).unwrap();
+
+        let (decoded, _) = decode("wide.png", data.into_inner()).unwrap();
+        let settings = ExportSettings { format: ExportFormat::Tiff, bit_depth: BitDepth::Sixteen, ..ExportSettings::default() };
+        let exported = image::load_from_memory(&encode(decoded, &settings).unwrap()).unwrap();
+        assert_eq!(exported.into_rgba16(), img);
+    }
+
+    #[test]
+    fn output_profile_is_embedded() {
+        let img = RgbaImage::from_fn(8, 8, |x, y| Rgba([x as u8 * 30, y as u8 * 30, 200, 255]));
+        for format in [ExportFormat::Png, ExportFormat::Jpeg] {
+            let settings = ExportSettings { format, profile: OutputProfile::DisplayP3, ..ExportSettings::default() };
+            let (_, metadata) = decode("exported", encode(fairplay_core::from_rgba8(&img), &settings).unwrap()).unwrap();
+            assert_eq!(metadata.summary, vec![(String::from("Colour profile"), String::from("Display P3 (converted to sRGB)"))]);
+        }
+        // An sRGB profile is named, but nothing was converted.
+        let settings = ExportSettings { format: ExportFormat::Png, profile: OutputProfile::Srgb, ..ExportSettings::default() };
+        let (_, metadata) = decode("exported", encode(fairplay_core::from_rgba8(&img), &settings).unwrap()).unwrap();
+        assert_eq!(metadata.summary, vec![(String::from("Colour profile"), String::from("sRGB"))]);
+    }
+
+    #[test]
+    fn encodes_lossy_webp() {
+        let img = RgbaImage::from_fn(16, 16, |x, y| Rgba([x as u8 * 16, y as u8 * 16, 10, 255]));
+        let settings = ExportSettings { format: ExportFormat::WebP, webp_lossless: false, ..ExportSettings::default() };
+        let data = encode(fairplay_core::from_rgba8(&img), &settings).unwrap();
+        assert_eq!(image::guess_format(&data).unwrap(), ImageFormat::WebP);
+    }
+
+    #[test]
+    fn every_readable_format_can_be_read() {
+        for (_, format) in READABLE_FORMATS {
+            assert!(format.can_read(), "{:?}", format);
+            assert!(!format.extensions_str().is_empty());
+        }
+        assert_eq!(ImageFormat::from_extension("tif"), Some(ImageFormat::Tiff));
+        assert_eq!(ImageFormat::from_extension("jpeg"), Some(ImageFormat::Jpeg));
+    }
+}
@@ -0,0 +1,210 @@
+use std::io::Cursor;
+
+use exif::{Exif, Field, In, Reader, Tag, Value};
+use exif::experimental::Writer;
+use image::DynamicImage;
+
+use crate::models::export::ExportFormat;
+use crate::models::metadata::Metadata;
+use crate::services::container;
+
+/// Fields shown in the info panel.
+const SUMMARY: [(&str, Tag); 7] = [
+    ("Lens", Tag::LensModel),
+    ("Exposure", Tag::ExposureTime),
+    ("Aperture", Tag::FNumber),
+    ("ISO", Tag::PhotographicSensitivity),
+    ("Focal length", Tag::FocalLength),
+    ("Date", Tag::DateTimeOriginal),
+    ("Software", Tag::Software),
+];
+
+/// TIFF fields describing how the pixels of the source were stored, which the exported file
+/// doesn't share.
+const STRUCTURAL: [Tag; 9] = [
+    Tag::ImageWidth,
+    Tag::ImageLength,
+    Tag::BitsPerSample,
+    Tag::Compression,
+    Tag::PhotometricInterpretation,
+    Tag::SamplesPerPixel,
+    Tag::RowsPerStrip,
+    Tag::PlanarConfiguration,
+    Tag::YCbCrSubSampling,
+];
+
+/// Reads the EXIF data of an encoded image. Returns the metadata together with the EXIF
+/// orientation (1 when there is none), which still has to be applied with [`orient`].
+pub fn read(data: &[u8]) -> (Metadata, u32) {
+    let Ok(exif) = Reader::new().read_from_container(&mut Cursor::new(data)) else {
+        return (Metadata::default(), 1)
+    };
+
+    let orientation = exif.get_field(Tag::Orientation, In::PRIMARY)
+        .and_then(|f| f.value.get_uint(0))
+        .filter(|o| (1..=8).contains(o))
+        .unwrap_or(1);
+
+    let mut summary = vec![];
+    let camera = [Tag::Make, Tag::Model].iter()
+        .filter_map(|tag| exif.get_field(*tag, In::PRIMARY))
+        .map(|field| display(&exif, field))
+        .collect::<Vec<_>>()
+        .join(" ");
+    if !camera.is_empty() {
+        summary.push((String::from("Camera"), camera));
+    }
+    for (name, tag) in SUMMARY {
+        if let Some(field) = exif.get_field(tag, In::PRIMARY) {
+            summary.push((String::from(name), display(&exif, field)));
+        }
+    }
+
+    (Metadata { exif: rewrite(&exif), summary }, orientation)
+}
+
+fn display(exif: &Exif, field: &Field) -> String {
+    match &field.value {
+        Value::Ascii(values) => {
+            values.iter()
+                .map(|v| String::from_utf8_lossy(v).trim().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+        _ => { field.display_value().with_unit(exif).to_string() }
+    }
+}
+
+/// Re-encodes the primary image fields with a normal orientation. The thumbnail, the pixel
+/// dimensions, the storage layout and the maker note are dropped, as they no longer match after
+/// editing. Fields of types the writer doesn't know are skipped.
+fn rewrite(exif: &Exif) -> Option<Vec<u8>> {
+    let orientation = Field {
+        tag: Tag::Orientation,
+        ifd_num: In::PRIMARY,
+        value: Value::Short(vec![1]),
+    };
+
+    let mut writer = Writer::new();
+    writer.push_field(&orientation);
+    for field in exif.fields() {
+        if field.ifd_num != In::PRIMARY {
+            continue;
+        }
+        if matches!(field.tag, Tag::Orientation | Tag::PixelXDimension | Tag::PixelYDimension | Tag::MakerNote) {
+            continue;
+        }
+        if STRUCTURAL.contains(&field.tag) || matches!(field.value, Value::Unknown(..)) {
+            continue;
+        }
+        writer.push_field(field);
+    }
+
+    let mut data = Cursor::new(Vec::new());
+    writer.write(&mut data, exif.little_endian()).ok()?;
+    Some(data.into_inner())
+}
+
+/// Rotates and mirrors `img` so that it is displayed upright for the given EXIF orientation.
+pub fn orient(img: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => { img.fliph() }
+        3 => { img.rotate180() }
+        4 => { img.flipv() }
+        5 => { img.rotate90().fliph() }
+        6 => { img.rotate90() }
+        7 => { img.rotate270().fliph() }
+        8 => { img.rotate270() }
+        _ => { img }
+    }
+}
+
+/// Inserts an EXIF block into an encoded image. Formats without support are returned as they are.
+pub fn embed(data: Vec<u8>, format: ExportFormat, exif: &[u8]) -> Vec<u8> {
+    let embedded = match format {
+        ExportFormat::Jpeg => {
+            let mut payload = b"Exif\0\0".to_vec();
+            payload.extend_from_slice(exif);
+            container::insert_jpeg_segment(&data, 0xE1, &payload)
+        }
+        ExportFormat::Png => { container::insert_png_chunk(&data, b"eXIf", exif) }
+        _ => { None }
+    };
+    embedded.unwrap_or(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use crate::models::export::ExportSettings;
+    use crate::services::file::encode;
+
+    use super::*;
+
+    fn exif(orientation: u16) -> Vec<u8> {
+        let fields = [
+            Field { tag: Tag::Orientation, ifd_num: In::PRIMARY, value: Value::Short(vec![orientation]) },
+            Field { tag: Tag::Make, ifd_num: In::PRIMARY, value: Value::Ascii(vec![b"Fair".to_vec()]) },
+            Field { tag: Tag::Model, ifd_num: In::PRIMARY, value: Value::Ascii(vec![b"Play 1".to_vec()]) },
+        ];
+        let mut writer = Writer::new();
+        fields.iter().for_each(|f| writer.push_field(f));
+        let mut data = Cursor::new(Vec::new());
+        writer.write(&mut data, false).unwrap();
+        data.into_inner()
+    }
+
+    #[test]
+    fn orientations_match_their_transforms() {
+        let img = RgbaImage::from_fn(3, 2, |x, y| Rgba([x as u8, y as u8, 0, 255]));
+        for orientation in 1..=8 {
+            let oriented = orient(DynamicImage::ImageRgba8(img.clone()), orientation);
+            let transposed = orientation >= 5;
+            assert_eq!((oriented.width(), oriented.height()), if transposed { (2, 3) } else { (3, 2) });
+        }
+        // Orientation 6 is stored rotated 90° counter-clockwise: the first row becomes the last column.
+        let rotated = orient(DynamicImage::ImageRgba8(img.clone()), 6).into_rgba8();
+        assert_eq!(*rotated.get_pixel(1, 0), *img.get_pixel(0, 0));
+        assert_eq!(*rotated.get_pixel(0, 0), *img.get_pixel(0, 1));
+    }
+
+    #[test]
+    fn only_descriptive_fields_are_rewritten() {
+        // A little endian TIFF header and one IFD, Model having a type the writer doesn't know.
+        let mut tiff = vec![b'I', b'I', 42, 0, 8, 0, 0, 0, 4, 0];
+        for (tag, typ, count, value) in [(0x100u16, 4u16, 1u32, 6000u32), (0x103, 3, 1, 1), (0x10f, 2, 4, u32::from_le_bytes(*b"Fai\0")), (0x110, 99, 1, 0)] {
+            tiff.extend_from_slice(&tag.to_le_bytes());
+            tiff.extend_from_slice(&typ.to_le_bytes());
+            tiff.extend_from_slice(&count.to_le_bytes());
+            tiff.extend_from_slice(&value.to_le_bytes());
+        }
+        tiff.extend_from_slice(&[0; 4]);
+        let exif = Reader::new().read_raw(tiff).unwrap();
+        let data = rewrite(&exif).unwrap();
+
+        let rewritten = Reader::new().read_raw(data).unwrap();
+        assert!(rewritten.get_field(Tag::Make, In::PRIMARY).is_some());
+        for tag in [Tag::ImageWidth, Tag::Compression, Tag::Model] {
+            assert!(rewritten.get_field(tag, In::PRIMARY).is_none());
+        }
+    }
+
+    #[test]
+    fn embedded_exif_is_read_back() {
+        let img = fairplay_core::from_rgba8(&RgbaImage::from_fn(4, 4, |x, y| Rgba([x as u8 * 60, y as u8 * 60, 0, 255])));
+        for format in [ExportFormat::Jpeg, ExportFormat::Png] {
+            let settings = ExportSettings { format, ..ExportSettings::default() };
+            let data = embed(encode(img.clone(), &settings).unwrap(), format, &exif(6));
+            assert!(image::load_from_memory_with_format(&data, format.image_format()).is_ok());
+
+            let (metadata, orientation) = read(&data);
+            assert_eq!(orientation, 6);
+            assert_eq!(metadata.summary[0], (String::from("Camera"), String::from("Fair Play 1")));
+
+            // The re-encoded block has the orientation reset.
+            let data = embed(encode(img.clone(), &settings).unwrap(), format, &metadata.exif.unwrap());
+            assert_eq!(read(&data).1, 1);
+        }
+    }
+}
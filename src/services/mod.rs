@@ -1,2 +1,5 @@
+pub mod color;
+mod container;
+pub mod file;
 pub mod image;
-mod functions;
\ No newline at end of file
+pub mod metadata;